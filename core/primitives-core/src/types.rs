@@ -1,3 +1,5 @@
+use borsh::{BorshDeserialize, BorshSerialize};
+
 use crate::hash::CryptoHash;
 
 /// Account identifier. Provides access to user's state.
@@ -16,6 +18,8 @@ pub type StorageUsageChange = i64;
 pub type Nonce = u64;
 /// Height of the block.
 pub type BlockHeight = u64;
+/// Timestamp in nanoseconds since the Unix epoch, e.g. of a block header.
+pub type TimestampNanos = u64;
 /// Height of the epoch.
 pub type EpochHeight = u64;
 /// Shard index, from 0 to NUM_SHARDS - 1.
@@ -27,7 +31,7 @@ pub type Gas = u64;
 
 /// Weight of unused gas to distribute to scheduled function call actions.
 /// Used in `promise_batch_action_function_call_weight` host function.
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, BorshSerialize, BorshDeserialize)]
 pub struct GasWeight(pub u64);
 
 /// Result from a gas distribution among function calls with ratios.
@@ -39,6 +43,15 @@ pub enum GasDistribution {
     All,
     /// There were no function call actions with a ratio specified.
     NoRatios,
+    /// All remaining gas was distributed to functions, but the gas of at least one function
+    /// call was clamped to the maximum value instead of overflowing.
+    Saturated,
+    /// All remaining gas was distributed to functions, but it didn't cover the minimum gas of
+    /// every function call, so it was split among them in proportion to their minimums.
+    MinGasNotCovered,
+    /// Only part of the remaining gas was distributed to functions, the rest didn't fit under
+    /// their gas ceilings or didn't lift them above their prepaid gas, and stays unused.
+    Partial { undistributed: Gas },
 }
 
 /// Number of blocks in current group.
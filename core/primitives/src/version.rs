@@ -164,6 +164,15 @@ pub enum ProtocolFeature {
     /// Charge for contract loading before it happens.
     #[cfg(feature = "protocol_feature_fix_contract_loading_cost")]
     FixContractLoadingCost,
+    /// Report why a public key passed to a promise action can't be decoded.
+    #[cfg(feature = "protocol_feature_public_key_error_reason")]
+    PublicKeyErrorReason,
+    /// Report the offset of the invalid UTF-8 in method names passed to promise actions.
+    #[cfg(feature = "protocol_feature_method_name_error_offset")]
+    MethodNameErrorOffset,
+    /// Reject delete account actions transferring the funds to the system account.
+    #[cfg(feature = "protocol_feature_reject_system_beneficiary")]
+    RejectSystemBeneficiary,
 }
 
 /// Both, outgoing and incoming tcp connections to peers, will be rejected if `peer's`
@@ -180,7 +189,7 @@ const STABLE_PROTOCOL_VERSION: ProtocolVersion = 55;
 pub const PROTOCOL_VERSION: ProtocolVersion = STABLE_PROTOCOL_VERSION;
 /// Current latest nightly version of the protocol.
 #[cfg(feature = "nightly_protocol")]
-pub const PROTOCOL_VERSION: ProtocolVersion = 129;
+pub const PROTOCOL_VERSION: ProtocolVersion = 132;
 
 /// The points in time after which the voting for the protocol version should start.
 #[allow(dead_code)]
@@ -246,6 +255,12 @@ impl ProtocolFeature {
             ProtocolFeature::FixStakingThreshold => 126,
             #[cfg(feature = "protocol_feature_fix_contract_loading_cost")]
             ProtocolFeature::FixContractLoadingCost => 129,
+            #[cfg(feature = "protocol_feature_public_key_error_reason")]
+            ProtocolFeature::PublicKeyErrorReason => 130,
+            #[cfg(feature = "protocol_feature_method_name_error_offset")]
+            ProtocolFeature::MethodNameErrorOffset => 131,
+            #[cfg(feature = "protocol_feature_reject_system_beneficiary")]
+            ProtocolFeature::RejectSystemBeneficiary => 132,
         }
     }
 }
@@ -224,6 +224,28 @@ pub enum HostError {
     /// Invalid input to alt_bn128 familiy of functions (e.g., point which isn't
     /// on the curve).
     AltBn128InvalidInput { msg: String },
+    /// The receipt `receipt_index` transitively depends on its own output data.
+    ReceiptDependencyCycle { receipt_index: u64 },
+    /// A function call access key was given an allowance of zero, which can never pay for a call
+    ZeroAllowance,
+    /// `External` generated a data id which is already used by another receipt
+    DuplicateDataId,
+    /// Action index is out of the bounds of the receipt's actions
+    InvalidActionIndex { action_index: u64 },
+    /// VM Logic provided a public key which can't be decoded, `msg` describes why
+    InvalidPublicKeyWithReason { msg: String },
+    /// VM Logic provided a method name which is not valid UTF-8 from the byte at `valid_up_to`
+    InvalidMethodNameUtf8 { valid_up_to: u64 },
+    /// Number of actions across all receipts exceeds the limit
+    NumberActionsExceeded { limit: u64 },
+    /// An access key with the same public key and nonce was already added by this contract call
+    DuplicateAccessKeyNonce { nonce: u64 },
+    /// Receipts to the given receiver are forbidden in this context
+    InvalidReceiverId { account_id: String },
+    /// A stake action with a zero amount was appended without explicitly unstaking
+    ZeroStake,
+    /// A function call access key lists the same method name more than once
+    DuplicateMethodName { method_name: String },
 }
 
 #[derive(Debug, PartialEq)]
@@ -421,6 +443,17 @@ impl std::fmt::Display for HostError {
             Deprecated {method_name}=> write!(f, "Attempted to call deprecated host function {}", method_name),
             AltBn128InvalidInput { msg } => write!(f, "AltBn128 invalid input: {}", msg),
             ECRecoverError { msg } => write!(f, "ECDSA recover error: {}", msg),
+            ReceiptDependencyCycle { receipt_index } => write!(f, "Receipt {:?} transitively depends on its own output data", receipt_index),
+            ZeroAllowance => write!(f, "The allowance of a function call access key must be either unlimited or positive"),
+            DuplicateDataId => write!(f, "The generated data id is already used by another receipt"),
+            InvalidActionIndex { action_index } => write!(f, "{:?} does not correspond to an action position of the receipt", action_index),
+            InvalidPublicKeyWithReason { msg } => write!(f, "VM Logic provided an invalid public key: {}", msg),
+            InvalidMethodNameUtf8 { valid_up_to } => write!(f, "VM Logic provided an invalid method name: invalid UTF-8 at byte {}", valid_up_to),
+            NumberActionsExceeded { limit } => write!(f, "Total number of actions exceeded the limit {}", limit),
+            ZeroStake => write!(f, "Stake amount must be positive, unstaking has to be explicit"),
+            InvalidReceiverId { account_id } => write!(f, "Receipts to {} are not allowed", account_id),
+            DuplicateAccessKeyNonce { nonce } => write!(f, "An access key with the same public key and nonce {} was already added", nonce),
+            DuplicateMethodName { method_name } => write!(f, "Method name {} is listed more than once", method_name),
         }
     }
 }
@@ -128,6 +128,13 @@ impl<'a> VMLogic<'a> {
             context.prepaid_gas,
             context.is_view(),
         );
+        let mut receipt_manager = ReceiptManager::default();
+        // Legacy code to accept the system account as beneficiary in old protocol versions.
+        receipt_manager.set_allow_system_beneficiary(!checked_feature!(
+            "protocol_feature_reject_system_beneficiary",
+            RejectSystemBeneficiary,
+            current_protocol_version
+        ));
         Self {
             ext,
             context,
@@ -145,7 +152,7 @@ impl<'a> VMLogic<'a> {
             promises: vec![],
             total_log_length: 0,
             current_protocol_version,
-            receipt_manager: ReceiptManager::default(),
+            receipt_manager,
         }
     }
 
@@ -1671,14 +1678,15 @@ impl<'a> VMLogic<'a> {
 
         self.deduct_balance(amount)?;
 
-        self.receipt_manager.append_action_function_call_weight(
+        let result = self.receipt_manager.append_action_function_call_weight(
             receipt_idx,
             method_name,
             arguments,
             amount,
             gas,
             GasWeight(gas_weight),
-        )
+        );
+        self.host_error_compat(result)
     }
 
     /// Appends `Transfer` action to the batch of actions for the given promise pointed by
@@ -1773,8 +1781,8 @@ impl<'a> VMLogic<'a> {
             ActionCosts::stake,
         )?;
 
-        self.receipt_manager.append_action_stake(receipt_idx, amount, public_key)?;
-        Ok(())
+        let result = self.receipt_manager.append_action_stake(receipt_idx, amount, public_key);
+        self.host_error_compat(result)
     }
 
     /// Appends `AddKey` action to the batch of actions for the given promise pointed by
@@ -1818,12 +1826,12 @@ impl<'a> VMLogic<'a> {
             ActionCosts::add_key,
         )?;
 
-        self.receipt_manager.append_action_add_key_with_full_access(
+        let result = self.receipt_manager.append_action_add_key_with_full_access(
             receipt_idx,
             public_key,
             nonce,
-        )?;
-        Ok(())
+        );
+        self.host_error_compat(result)
     }
 
     /// Appends `AddKey` action to the batch of actions for the given promise pointed by
@@ -1888,15 +1896,15 @@ impl<'a> VMLogic<'a> {
             ActionCosts::add_key,
         )?;
 
-        self.receipt_manager.append_action_add_key_with_function_call(
+        let result = self.receipt_manager.append_action_add_key_with_function_call(
             receipt_idx,
             public_key,
             nonce,
             allowance,
             receiver_id,
             method_names,
-        )?;
-        Ok(())
+        );
+        self.host_error_compat(result)
     }
 
     /// Appends `DeleteKey` action to the batch of actions for the given promise pointed by
@@ -1939,8 +1947,8 @@ impl<'a> VMLogic<'a> {
             ActionCosts::delete_key,
         )?;
 
-        self.receipt_manager.append_action_delete_key(receipt_idx, public_key)?;
-        Ok(())
+        let result = self.receipt_manager.append_action_delete_key(receipt_idx, public_key);
+        self.host_error_compat(result)
     }
 
     /// Appends `DeleteAccount` action to the batch of actions for the given promise pointed by
@@ -2649,8 +2657,17 @@ impl<'a> VMLogic<'a> {
 
             // Spend all remaining gas by distributing it among function calls that specify
             // a gas weight
-            if let GasDistribution::All = self.receipt_manager.distribute_unused_gas(unused_gas) {
-                self.gas_counter.prepay_gas(unused_gas).unwrap();
+            match self.receipt_manager.distribute_unused_gas(unused_gas) {
+                GasDistribution::All
+                | GasDistribution::Saturated
+                | GasDistribution::MinGasNotCovered => {
+                    self.gas_counter.prepay_gas(unused_gas).unwrap();
+                }
+                // Gas left out of the distribution stays unused and is refunded.
+                GasDistribution::Partial { undistributed } => {
+                    self.gas_counter.prepay_gas(unused_gas - undistributed).unwrap();
+                }
+                _ => {}
             }
         }
 
@@ -2668,7 +2685,7 @@ impl<'a> VMLogic<'a> {
             used_gas,
             logs: self.logs,
             profile,
-            action_receipts: self.receipt_manager.action_receipts,
+            action_receipts: self.receipt_manager.take_receipts(),
         }
     }
 
@@ -2730,6 +2747,33 @@ impl<'a> VMLogic<'a> {
         Ok(())
     }
 
+    /// Legacy code to preserve the less detailed errors of old protocol versions: the
+    /// reasonless [`HostError::InvalidPublicKey`] for undecodable public keys and
+    /// [`HostError::InvalidMethodName`] without the position of invalid UTF-8.
+    fn host_error_compat(&self, result: Result<()>) -> Result<()> {
+        result.map_err(|err| match err {
+            VMLogicError::HostError(HostError::InvalidPublicKeyWithReason { .. })
+                if !checked_feature!(
+                    "protocol_feature_public_key_error_reason",
+                    PublicKeyErrorReason,
+                    self.current_protocol_version
+                ) =>
+            {
+                HostError::InvalidPublicKey.into()
+            }
+            VMLogicError::HostError(HostError::InvalidMethodNameUtf8 { .. })
+                if !checked_feature!(
+                    "protocol_feature_method_name_error_offset",
+                    MethodNameErrorOffset,
+                    self.current_protocol_version
+                ) =>
+            {
+                HostError::InvalidMethodName.into()
+            }
+            err => err,
+        })
+    }
+
     /// Legacy code to preserve old gas charging behaviour in old protocol versions.
     pub fn after_loading_executable(
         &mut self,
@@ -1,9 +1,9 @@
 use crate::logic;
 use crate::types::ReceiptIndex;
 use crate::External;
-use borsh::BorshDeserialize;
+use borsh::{BorshDeserialize, BorshSerialize};
 use near_crypto::PublicKey;
-use near_primitives::receipt::DataReceiver;
+use near_primitives::receipt::{ActionReceipt, DataReceiver, Receipt, ReceiptEnum};
 use near_primitives::transaction::{
     Action, AddKeyAction, CreateAccountAction, DeleteAccountAction, DeleteKeyAction,
     DeployContractAction, FunctionCallAction, StakeAction, TransferAction,
@@ -11,13 +11,28 @@ use near_primitives::transaction::{
 use near_primitives::types::{Balance, Nonce};
 use near_primitives_core::account::{AccessKey, AccessKeyPermission, FunctionCallPermission};
 use near_primitives_core::hash::CryptoHash;
+use near_primitives_core::runtime::fees::{
+    transfer_send_fee, ActionCreationConfig, RuntimeFeesConfig,
+};
+use near_primitives_core::serialize::{option_u128_dec_format, u128_dec_format};
 use near_primitives_core::types::{AccountId, Gas};
 use near_primitives_core::types::{GasDistribution, GasWeight};
 use near_vm_errors::HostError;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::sync::Arc;
 
 type ActionReceipts = Vec<(AccountId, ReceiptMetadata)>;
 
-#[derive(Debug, Clone, PartialEq)]
+/// Callback invoked with the index of a receipt, see [`ReceiptManager::set_on_receipt_ready`].
+pub type ReceiptReadyCallback = Arc<dyn Fn(ReceiptIndex) + Send + Sync>;
+
+/// Callback invoked with an estimated gas cost, see
+/// [`ReceiptManager::set_construction_gas_hook`].
+pub type ConstructionGasCallback = Arc<dyn Fn(Gas) + Send + Sync>;
+
+#[derive(Debug, Clone, PartialEq, BorshSerialize, BorshDeserialize)]
 pub struct ReceiptMetadata {
     /// If present, where to route the output data
     pub output_data_receivers: Vec<DataReceiver>,
@@ -29,16 +44,220 @@ pub struct ReceiptMetadata {
     pub input_data_ids: Vec<CryptoHash>,
     /// A list of actions to process when all input_data_ids are filled
     pub actions: Vec<Action>,
+    /// Gas price the receipt is bought at if it differs from the gas price of the receipt
+    /// creating it, see [`ReceiptManager::set_receipt_gas_price`].
+    pub gas_price: Option<Balance>,
+}
+
+/// Description of an action to append with [`ReceiptManager::append_action_from_spec`], with
+/// the same arguments as the corresponding `append_action_*` method.
+///
+/// Public keys are borsh-serialized like the ones passed by contracts, so they go through the
+/// same validation. Balances are serialized as decimal strings, like in RPC views.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ActionSpec {
+    CreateAccount,
+    DeployContract {
+        code: Vec<u8>,
+    },
+    FunctionCall {
+        method_name: String,
+        args: Vec<u8>,
+        #[serde(with = "u128_dec_format")]
+        attached_deposit: Balance,
+        prepaid_gas: Gas,
+        gas_weight: u64,
+    },
+    Transfer {
+        #[serde(with = "u128_dec_format")]
+        deposit: Balance,
+    },
+    Stake {
+        #[serde(with = "u128_dec_format")]
+        stake: Balance,
+        public_key: Vec<u8>,
+    },
+    AddKeyWithFullAccess {
+        public_key: Vec<u8>,
+        nonce: Nonce,
+    },
+    AddKeyWithFunctionCall {
+        public_key: Vec<u8>,
+        nonce: Nonce,
+        #[serde(with = "option_u128_dec_format")]
+        allowance: Option<Balance>,
+        receiver_id: AccountId,
+        method_names: Vec<String>,
+    },
+    DeleteKey {
+        public_key: Vec<u8>,
+    },
+    DeleteAccount {
+        beneficiary_id: AccountId,
+    },
+}
+
+/// Noteworthy properties of the receipts returned by
+/// [`ReceiptManager::take_receipts_with_diagnostics`].
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct TakeReceiptsDiagnostics {
+    /// Indices of the receipts addressed to their predecessor, i.e. calls of a contract to
+    /// itself. Such receipts are valid, but may point at unintended re-entrancy.
+    pub reflexive_receipts: Vec<ReceiptIndex>,
 }
 
-#[derive(Default, Clone, PartialEq)]
-pub(crate) struct ReceiptManager {
+/// Receipts can be snapshotted with borsh, e.g. to persist them across calls. Everything but
+/// the hooks set with [`ReceiptManager::set_metrics`] and
+/// [`ReceiptManager::set_on_receipt_ready`] and [`ReceiptManager::set_construction_gas_hook`]
+/// and the predicate set with [`ReceiptManager::set_forbidden_receiver`] round-trips.
+#[derive(Default, Clone, PartialEq, BorshSerialize, BorshDeserialize)]
+pub struct ReceiptManager {
     pub(crate) action_receipts: ActionReceipts,
-    gas_weights: Vec<(FunctionCallActionIndex, GasWeight)>,
+    gas_weights: Vec<GasWeightEntry>,
+    /// Placeholder actions reserved with [`ReceiptManager::reserve_action_slot`] and not
+    /// filled yet.
+    action_slots: Vec<FunctionCallActionIndex>,
+    /// Sum of the deposits attached by transfer and function call actions so far.
+    attached_deposit: Balance,
+    /// Optional cap on `attached_deposit`, see [`ReceiptManager::set_deposit_limit`].
+    deposit_limit: Option<Balance>,
+    /// Optional cap on the size of deployed code, see
+    /// [`ReceiptManager::set_max_contract_size`].
+    max_contract_size: Option<u64>,
+    /// Optional cap on the number of actions across all receipts, see
+    /// [`ReceiptManager::set_max_total_actions`].
+    max_total_actions: Option<u64>,
+    /// Optional number of actions across all receipts from which the manager reports being
+    /// full without rejecting actions, see [`ReceiptManager::set_soft_max_total_actions`].
+    soft_max_total_actions: Option<u64>,
+    /// Whether adding an access key with an already used public key and nonce fails, see
+    /// [`ReceiptManager::set_reject_duplicate_key_nonces`].
+    reject_duplicate_key_nonces: bool,
+    /// Whether stake actions with a zero amount are rejected unless appended as an explicit
+    /// unstake, see [`ReceiptManager::set_reject_zero_stake`].
+    reject_zero_stake: bool,
+    /// Whether function call access keys listing a method name more than once are rejected,
+    /// see [`ReceiptManager::set_reject_duplicate_method_names`].
+    reject_duplicate_method_names: bool,
+    /// Whether delete account actions may transfer the funds to the system account, see
+    /// [`ReceiptManager::set_allow_system_beneficiary`].
+    allow_system_beneficiary: bool,
+    /// Whether taken receipts have their actions put in canonical order, see
+    /// [`ReceiptManager::set_sort_actions_on_take`].
+    sort_actions_on_take: bool,
+    /// Optional predicate on the receivers of new receipts, see
+    /// [`ReceiptManager::set_forbidden_receiver`].
+    #[borsh_skip]
+    forbidden_receiver: ForbiddenReceiver,
+    /// Gas price of the receipts produced by [`ReceiptManager::take_receipts_simple`], see
+    /// [`ReceiptManager::with_gas_price`].
+    gas_price: Option<Balance>,
+    #[borsh_skip]
+    metrics: MetricsHook,
+    #[borsh_skip]
+    on_receipt_ready: ReceiptReadyHook,
+    #[borsh_skip]
+    construction_gas: ConstructionGasHook,
+}
+
+/// Observer of [`ReceiptManager`] operations, e.g. to export metrics of promise construction.
+///
+/// All methods do nothing by default.
+pub trait ReceiptManagerMetrics: Send + Sync {
+    /// Called after a receipt is created, with the number of receipts it depends on.
+    fn receipt_created(&self, _num_dependencies: usize) {}
+
+    /// Called after an action is appended to the receipt `receipt_index`.
+    fn action_appended(&self, _receipt_index: ReceiptIndex) {}
+
+    /// Called before `unused_gas` is distributed among `num_function_calls` function calls.
+    fn unused_gas_distributed(&self, _unused_gas: Gas, _num_function_calls: usize) {}
+}
+
+/// Optional [`ReceiptManagerMetrics`] of a [`ReceiptManager`].
+///
+/// Metrics don't affect the receipts, so all hooks compare equal.
+#[derive(Default, Clone)]
+struct MetricsHook(Option<Arc<dyn ReceiptManagerMetrics>>);
+
+impl PartialEq for MetricsHook {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+impl MetricsHook {
+    fn with(&self, f: impl FnOnce(&dyn ReceiptManagerMetrics)) {
+        if let Some(metrics) = &self.0 {
+            f(metrics.as_ref());
+        }
+    }
+}
+
+/// Optional callback of a [`ReceiptManager`], invoked with the index of a receipt once it
+/// holds `num_actions` actions, see [`ReceiptManager::set_on_receipt_ready`].
+///
+/// Like [`MetricsHook`], all hooks compare equal.
+#[derive(Default, Clone)]
+struct ReceiptReadyHook(Option<(usize, ReceiptReadyCallback)>);
+
+impl PartialEq for ReceiptReadyHook {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+/// Optional callback of a [`ReceiptManager`], invoked with the estimated gas cost of each
+/// receipt and action it constructs, see [`ReceiptManager::set_construction_gas_hook`].
+///
+/// Like [`MetricsHook`], all hooks compare equal.
+#[derive(Default, Clone)]
+struct ConstructionGasHook(Option<(Arc<RuntimeFeesConfig>, ConstructionGasCallback)>);
+
+impl PartialEq for ConstructionGasHook {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+/// Optional predicate of a [`ReceiptManager`] on the receivers of new receipts, see
+/// [`ReceiptManager::set_forbidden_receiver`].
+///
+/// Function pointers don't compare meaningfully, so like [`MetricsHook`], all predicates
+/// compare equal.
+#[derive(Default, Clone)]
+struct ForbiddenReceiver(Option<fn(&AccountId) -> bool>);
+
+impl ForbiddenReceiver {
+    fn forbids(&self, receiver_id: &AccountId) -> bool {
+        self.0.map_or(false, |forbidden| forbidden(receiver_id))
+    }
+}
+
+impl PartialEq for ForbiddenReceiver {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+/// A function call action which receives a share of the unused gas.
+#[derive(Debug, Clone, PartialEq, BorshSerialize, BorshDeserialize)]
+struct GasWeightEntry {
+    index: FunctionCallActionIndex,
+    weight: GasWeight,
+    /// Upper bound on the gas distributed to the action, see
+    /// [`ReceiptManager::set_max_gas_per_action`].
+    max_gas_per_action: Option<Gas>,
+    /// Gas distributed to the action before the rest is split by weight, see
+    /// [`ReceiptManager::set_min_gas_per_action`].
+    min_gas_per_action: Option<Gas>,
+    /// Whether the prepaid gas of the action is a floor for its share of the unused gas rather
+    /// than a base the share is added to.
+    prepaid_gas_is_floor: bool,
 }
 
 /// Indexes the [`ReceiptManager`]'s action receipts and actions.
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, BorshSerialize, BorshDeserialize)]
 struct FunctionCallActionIndex {
     /// Index of [`ReceiptMetadata`] in the action receipts of [`ReceiptManager`].
     receipt_index: usize,
@@ -64,16 +283,285 @@ fn get_fuction_call_action_mut(
     }
 }
 
+fn get_function_call_action(
+    action_receipts: &ActionReceipts,
+    index: FunctionCallActionIndex,
+) -> &FunctionCallAction {
+    let FunctionCallActionIndex { receipt_index, action_index } = index;
+    if let Some(Action::FunctionCall(action)) = action_receipts
+        .get(receipt_index)
+        .and_then(|(_, receipt)| receipt.actions.get(action_index))
+    {
+        action
+    } else {
+        panic!(
+            "Invalid function call index \
+             (promise_index={receipt_index}, action_index={action_index})",
+        );
+    }
+}
+
+/// Estimated send fee of `action` sent to `receiver_id` from another account, like the fees
+/// charged by the runtime for the actions of a transaction.
+fn estimated_send_fee(cfg: &ActionCreationConfig, action: &Action, receiver_id: &AccountId) -> Gas {
+    match action {
+        Action::CreateAccount(_) => cfg.create_account_cost.send_fee(false),
+        Action::DeployContract(DeployContractAction { code }) => {
+            let num_bytes = code.len() as u64;
+            cfg.deploy_contract_cost.send_fee(false).saturating_add(
+                cfg.deploy_contract_cost_per_byte.send_fee(false).saturating_mul(num_bytes),
+            )
+        }
+        Action::FunctionCall(FunctionCallAction { method_name, args, .. }) => {
+            let num_bytes = method_name.len() as u64 + args.len() as u64;
+            cfg.function_call_cost.send_fee(false).saturating_add(
+                cfg.function_call_cost_per_byte.send_fee(false).saturating_mul(num_bytes),
+            )
+        }
+        Action::Transfer(_) => transfer_send_fee(cfg, false, receiver_id.is_implicit()),
+        Action::AddKey(AddKeyAction { access_key, .. }) => match &access_key.permission {
+            AccessKeyPermission::FunctionCall(FunctionCallPermission { method_names, .. }) => {
+                // Account for null-terminating characters.
+                let num_bytes = method_names.iter().map(|name| name.len() as u64 + 1).sum::<u64>();
+                cfg.add_key_cost.function_call_cost.send_fee(false).saturating_add(
+                    cfg.add_key_cost
+                        .function_call_cost_per_byte
+                        .send_fee(false)
+                        .saturating_mul(num_bytes),
+                )
+            }
+            AccessKeyPermission::FullAccess => cfg.add_key_cost.full_access_cost.send_fee(false),
+        },
+        Action::DeleteKey(_) => cfg.delete_key_cost.send_fee(false),
+        Action::DeleteAccount(_) => cfg.delete_account_cost.send_fee(false),
+        // Stake, and feature-gated stake actions which are never created by the receipt manager.
+        #[allow(unreachable_patterns)]
+        _ => cfg.stake_cost.send_fee(false),
+    }
+}
+
+fn gcd(mut a: u64, mut b: u64) -> u64 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+/// Decodes the public key of an action, failing with [`HostError::InvalidPublicKeyWithReason`]
+/// which carries the decoding error.
+fn parse_public_key(public_key: &[u8]) -> logic::Result<PublicKey> {
+    PublicKey::try_from_slice(public_key)
+        .map_err(|err| HostError::InvalidPublicKeyWithReason { msg: err.to_string() }.into())
+}
+
+/// Decodes a method name of an action, failing with [`HostError::InvalidMethodNameUtf8`]
+/// which carries the position of the first invalid byte.
+fn parse_method_name(method_name: Vec<u8>) -> logic::Result<String> {
+    String::from_utf8(method_name).map_err(|err| {
+        let valid_up_to = err.utf8_error().valid_up_to() as u64;
+        HostError::InvalidMethodNameUtf8 { valid_up_to }.into()
+    })
+}
+
+/// Splits `gas` among `weights` proportionally, returning the share of each weight.
+///
+/// The remainder of the gas not assigned due to precision loss is added to the last share, so
+/// the shares sum up to `gas` unless all weights are zero, in which case nothing is assigned.
+pub(crate) fn distribute(weights: &[u64], gas: Gas) -> Vec<Gas> {
+    let weight_sum: u128 = weights.iter().map(|weight| *weight as u128).sum();
+    if weight_sum == 0 {
+        return vec![0; weights.len()];
+    }
+    // Multiplication is done in u128 with max values of u64::MAX so this cannot overflow.
+    // The quotient fits in 64 bits because weight_sum >= weight, but it is clamped to `Gas::MAX`
+    // rather than silently truncated should that ever change.
+    let mut shares: Vec<Gas> = weights
+        .iter()
+        .map(|weight| {
+            let share = gas as u128 * *weight as u128 / weight_sum;
+            Gas::try_from(share).unwrap_or(Gas::MAX)
+        })
+        .collect();
+    // The truncated shares sum up to at most `gas`.
+    let distributed: Gas = shares.iter().sum();
+    if let Some(last) = shares.last_mut() {
+        *last += gas - distributed;
+    }
+    shares
+}
+
+/// Returns whether `unused_gas` covers the minimum gas of all `entries`.
+fn min_gas_covered(entries: &[GasWeightEntry], unused_gas: Gas) -> bool {
+    let min_gas_sum: u128 =
+        entries.iter().map(|entry| entry.min_gas_per_action.unwrap_or(0) as u128).sum();
+    min_gas_sum <= unused_gas as u128
+}
+
+/// Splits `unused_gas` among `entries` with [`distribute`], returning the gas assigned to
+/// each entry.
+///
+/// Each entry is first assigned its minimum gas, then the rest is split by weight. If the
+/// minimums can't all be covered, `unused_gas` is split in proportion to them instead.
+///
+/// Entries whose total would exceed their ceiling are assigned the ceiling and the split is
+/// repeated for the remaining entries and gas, until no share exceeds its ceiling.
+fn split_unused_gas(entries: &[GasWeightEntry], unused_gas: Gas) -> Vec<Gas> {
+    let min_gas: Vec<Gas> =
+        entries.iter().map(|entry| entry.min_gas_per_action.unwrap_or(0)).collect();
+    if !min_gas_covered(entries, unused_gas) {
+        return distribute(&min_gas, unused_gas);
+    }
+    // The minimums sum up to at most `unused_gas`, so this cannot overflow.
+    let mut remaining_gas = unused_gas - min_gas.iter().sum::<Gas>();
+    let mut assigned = min_gas;
+    let mut uncapped: Vec<usize> = (0..entries.len()).collect();
+    while !uncapped.is_empty() {
+        let weights: Vec<u64> = uncapped.iter().map(|&i| entries[i].weight.0).collect();
+        let num_uncapped = uncapped.len();
+        let mut capped_gas = 0;
+        for (i, share) in
+            std::mem::take(&mut uncapped).into_iter().zip(distribute(&weights, remaining_gas))
+        {
+            let min_gas = entries[i].min_gas_per_action.unwrap_or(0);
+            match entries[i].max_gas_per_action.map(|max_gas| max_gas.saturating_sub(min_gas)) {
+                Some(max_share) if share > max_share => {
+                    assigned[i] = min_gas + max_share;
+                    capped_gas += max_share;
+                }
+                _ => {
+                    assigned[i] = min_gas + share;
+                    uncapped.push(i);
+                }
+            }
+        }
+        if uncapped.len() == num_uncapped {
+            break;
+        }
+        // The ceilings are below the shares, so they sum up to less than `remaining_gas`.
+        remaining_gas -= capped_gas;
+    }
+    assigned
+}
+
 impl ReceiptManager {
-    pub(crate) fn get_receipt_receiver(&self, receipt_index: ReceiptIndex) -> &AccountId {
+    /// Creates a manager whose receipts are all bought at `gas_price`, which
+    /// [`ReceiptManager::take_receipts_simple`] attaches to them.
+    pub fn with_gas_price(gas_price: Balance) -> Self {
+        Self { gas_price: Some(gas_price), ..Self::default() }
+    }
+
+    /// Makes the receipt `receipt_index` be bought at `gas_price` instead of the gas price of
+    /// the receipt creating it. The override is stored in [`ReceiptMetadata::gas_price`], so it
+    /// follows the receipt when receipt indices shift and is kept by every way of taking the
+    /// receipts.
+    ///
+    /// Fails with [`HostError::InvalidReceiptIndex`] if the receipt doesn't exist.
+    pub fn set_receipt_gas_price(
+        &mut self,
+        receipt_index: ReceiptIndex,
+        gas_price: Balance,
+    ) -> logic::Result<()> {
+        let (_, receipt) = self
+            .action_receipts
+            .get_mut(receipt_index as usize)
+            .ok_or(HostError::InvalidReceiptIndex { receipt_index })?;
+        receipt.gas_price = Some(gas_price);
+        Ok(())
+    }
+
+    /// Returns the receiver of the receipt `receipt_index`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the `receipt_index` does not refer to a known receipt.
+    pub fn get_receipt_receiver(&self, receipt_index: ReceiptIndex) -> &AccountId {
         self.action_receipts
             .get(receipt_index as usize)
             .map(|(id, _)| id)
             .expect("receipt index should be valid for getting receiver")
     }
 
+    /// Returns the index and the receiver of the most recently created receipt.
+    pub fn last_receipt(&self) -> Option<(ReceiptIndex, &AccountId)> {
+        let index = self.action_receipts.len().checked_sub(1)?;
+        Some((index as ReceiptIndex, &self.action_receipts[index].0))
+    }
+
+    /// Returns the ids of the data the receipt waits for, or `None` if there is no receipt with
+    /// the given index.
+    pub fn input_data_ids(&self, receipt_index: ReceiptIndex) -> Option<&[CryptoHash]> {
+        self.action_receipts
+            .get(receipt_index as usize)
+            .map(|(_, receipt)| receipt.input_data_ids.as_slice())
+    }
+
+    /// Returns where the receipt routes its output data, or `None` if there is no receipt with
+    /// the given index.
+    pub fn output_data_receivers(&self, receipt_index: ReceiptIndex) -> Option<&[DataReceiver]> {
+        self.action_receipts
+            .get(receipt_index as usize)
+            .map(|(_, receipt)| receipt.output_data_receivers.as_slice())
+    }
+
+    /// Returns the action at `action_index` of the receipt `receipt_index`, or `None` if either
+    /// index is out of bounds or the position is an unfilled action slot.
+    pub fn get_action(&self, receipt_index: ReceiptIndex, action_index: usize) -> Option<&Action> {
+        if self.is_action_slot(receipt_index as usize, action_index) {
+            return None;
+        }
+        self.action_receipts
+            .get(receipt_index as usize)
+            .and_then(|(_, receipt)| receipt.actions.get(action_index))
+    }
+
+    /// Returns the `(first, second)` indices of every pair of equal actions of the receipt
+    /// `receipt_index`, with `first < second`, or `None` if the receipt doesn't exist.
+    ///
+    /// Duplicates are not necessarily a mistake, e.g. two identical transfers are valid, so this
+    /// is only meant for warnings. Unfilled action slots are not compared.
+    pub fn duplicate_actions(&self, receipt_index: ReceiptIndex) -> Option<Vec<(usize, usize)>> {
+        let (_, receipt) = self.action_receipts.get(receipt_index as usize)?;
+        let actions: Vec<_> = self.filled_actions(receipt_index as usize, receipt).collect();
+        let mut duplicates = vec![];
+        for (position, (first, action)) in actions.iter().enumerate() {
+            for (second, other) in &actions[position + 1..] {
+                if action == other {
+                    duplicates.push((*first, *second));
+                }
+            }
+        }
+        Some(duplicates)
+    }
+
+    /// Returns whether other receipts depend on the receipt `receipt_index`, i.e. whether it
+    /// has output data receivers, or `None` if the receipt doesn't exist.
+    pub fn produces_output(&self, receipt_index: ReceiptIndex) -> Option<bool> {
+        self.action_receipts
+            .get(receipt_index as usize)
+            .map(|(_, receipt)| !receipt.output_data_receivers.is_empty())
+    }
+
+    /// Returns the index of the receipt producing the data with the given id, i.e. the receipt
+    /// with an output data receiver for `data_id`.
+    pub fn receipt_index_for_data_id(&self, data_id: &CryptoHash) -> Option<ReceiptIndex> {
+        self.action_receipts
+            .iter()
+            .position(|(_, receipt)| {
+                receipt.output_data_receivers.iter().any(|receiver| &receiver.data_id == data_id)
+            })
+            .map(|index| index as ReceiptIndex)
+    }
+
     /// Appends an action and returns the index the action was inserted in the receipt
-    fn append_action(&mut self, receipt_index: ReceiptIndex, action: Action) -> usize {
+    ///
+    /// Fails with [`HostError::NumberActionsExceeded`] if the receipts already hold as many
+    /// actions as allowed by [`ReceiptManager::set_max_total_actions`].
+    fn append_action(
+        &mut self,
+        receipt_index: ReceiptIndex,
+        action: Action,
+    ) -> logic::Result<usize> {
+        self.check_total_actions()?;
         let actions = &mut self
             .action_receipts
             .get_mut(receipt_index as usize)
@@ -82,9 +570,520 @@ impl ReceiptManager {
             .actions;
 
         actions.push(action);
+        let action_index = actions.len() - 1;
+        self.action_added(receipt_index, action_index);
 
         // Return index that action was inserted at
-        actions.len() - 1
+        Ok(action_index)
+    }
+
+    /// Reports the action added at `action_index` to the receipt `receipt_index` to the metrics
+    /// and the callbacks set with [`ReceiptManager::set_on_receipt_ready`] and
+    /// [`ReceiptManager::set_construction_gas_hook`].
+    fn action_added(&self, receipt_index: ReceiptIndex, action_index: usize) {
+        self.metrics.with(|metrics| metrics.action_appended(receipt_index));
+        if let Some((fees, hook)) = &self.construction_gas.0 {
+            let (receiver_id, receipt) = &self.action_receipts[receipt_index as usize];
+            hook(estimated_send_fee(
+                &fees.action_creation_config,
+                &receipt.actions[action_index],
+                receiver_id,
+            ));
+        }
+        if let Some((ready_at, callback)) = &self.on_receipt_ready.0 {
+            let (_, receipt) = &self.action_receipts[receipt_index as usize];
+            let num_actions = self.filled_actions(receipt_index as usize, receipt).count();
+            if num_actions == *ready_at {
+                callback(receipt_index);
+            }
+        }
+    }
+
+    /// Fails with [`HostError::NumberActionsExceeded`] if no more actions can be added without
+    /// exceeding the limit set with [`ReceiptManager::set_max_total_actions`].
+    fn check_total_actions(&self) -> logic::Result<()> {
+        match self.max_total_actions {
+            Some(limit) if self.total_action_count() as u64 >= limit => {
+                Err(HostError::NumberActionsExceeded { limit }.into())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Inserts `action` at `position` among the actions of the receipt `receipt_index`,
+    /// shifting the later actions.
+    ///
+    /// Gas weights of the shifted function call actions follow them to their new positions.
+    /// Deposits of transfer and function call actions count towards the deposit limit like
+    /// for the `append_action_*` methods, while the other limits are only checked by
+    /// [`ReceiptManager::validate_all`].
+    ///
+    /// Fails with [`HostError::InvalidReceiptIndex`] if the receipt doesn't exist and with
+    /// [`HostError::InvalidActionIndex`] if `position` is greater than the number of actions.
+    pub fn insert_action(
+        &mut self,
+        receipt_index: ReceiptIndex,
+        position: usize,
+        action: Action,
+    ) -> logic::Result<()> {
+        let num_actions = self
+            .action_receipts
+            .get(receipt_index as usize)
+            .ok_or(HostError::InvalidReceiptIndex { receipt_index })?
+            .1
+            .actions
+            .len();
+        if position > num_actions {
+            return Err(HostError::InvalidActionIndex { action_index: position as u64 }.into());
+        }
+        let attached_deposit = self.attached_deposit_with(&action)?;
+        self.check_total_actions()?;
+
+        self.action_receipts[receipt_index as usize].1.actions.insert(position, action);
+        self.attached_deposit = attached_deposit;
+        self.remap_action_indices(|mut index| {
+            if index.receipt_index == receipt_index as usize && index.action_index >= position {
+                index.action_index += 1;
+            }
+            Some(index)
+        });
+        self.action_added(receipt_index, position);
+        Ok(())
+    }
+
+    /// Moves the actions of the receipt `receipt_index` from position `at` onward into a new
+    /// receipt for the same receiver and returns the index of the new receipt.
+    ///
+    /// The data dependencies stay with the original receipt, the new receipt neither waits
+    /// for nor produces any data. Gas weights of the moved function call actions follow them
+    /// into the new receipt.
+    ///
+    /// Fails with [`HostError::InvalidReceiptIndex`] if the receipt doesn't exist and with
+    /// [`HostError::InvalidActionIndex`] if `at` is greater than the number of actions.
+    pub fn split_receipt(
+        &mut self,
+        receipt_index: ReceiptIndex,
+        at: usize,
+    ) -> logic::Result<ReceiptIndex> {
+        let (receiver_id, receipt) = self
+            .action_receipts
+            .get_mut(receipt_index as usize)
+            .ok_or(HostError::InvalidReceiptIndex { receipt_index })?;
+        if at > receipt.actions.len() {
+            return Err(HostError::InvalidActionIndex { action_index: at as u64 }.into());
+        }
+        let new_receipt = ReceiptMetadata {
+            output_data_receivers: vec![],
+            input_data_ids: vec![],
+            actions: receipt.actions.split_off(at),
+            gas_price: receipt.gas_price,
+        };
+        let receiver_id = receiver_id.clone();
+
+        let new_receipt_index = self.action_receipts.len();
+        self.action_receipts.push((receiver_id, new_receipt));
+        self.remap_action_indices(|index| {
+            if index.receipt_index == receipt_index as usize && index.action_index >= at {
+                Some(FunctionCallActionIndex {
+                    receipt_index: new_receipt_index,
+                    action_index: index.action_index - at,
+                })
+            } else {
+                Some(index)
+            }
+        });
+        self.metrics.with(|metrics| metrics.receipt_created(0));
+        Ok(new_receipt_index as ReceiptIndex)
+    }
+
+    /// Changes the receiver of the receipt `receipt_index` to `receiver_id`. The output data
+    /// receivers of the receipts it depends on are updated to route the data to the new
+    /// receiver.
+    ///
+    /// Fails with [`HostError::InvalidReceiptIndex`] if the receipt doesn't exist and with
+    /// [`HostError::InvalidReceiverId`] if the receiver is forbidden by
+    /// [`ReceiptManager::set_forbidden_receiver`].
+    pub fn set_receiver(
+        &mut self,
+        receipt_index: ReceiptIndex,
+        receiver_id: AccountId,
+    ) -> logic::Result<()> {
+        if receipt_index as usize >= self.action_receipts.len() {
+            return Err(HostError::InvalidReceiptIndex { receipt_index }.into());
+        }
+        if self.forbidden_receiver.forbids(&receiver_id) {
+            return Err(HostError::InvalidReceiverId { account_id: receiver_id.into() }.into());
+        }
+        let (receiver, receipt) = &mut self.action_receipts[receipt_index as usize];
+        *receiver = receiver_id.clone();
+        let input_data_ids = receipt.input_data_ids.clone();
+        for data_receiver in self
+            .action_receipts
+            .iter_mut()
+            .flat_map(|(_, receipt)| &mut receipt.output_data_receivers)
+            .filter(|data_receiver| input_data_ids.contains(&data_receiver.data_id))
+        {
+            data_receiver.receiver_id = receiver_id.clone();
+        }
+        Ok(())
+    }
+
+    /// Appends a placeholder action to the receipt `receipt_index` and returns its index, so
+    /// that the index is known before the action itself. The placeholder has to be replaced
+    /// with [`ReceiptManager::fill_action_slot`].
+    ///
+    /// The placeholder counts towards the limit set with
+    /// [`ReceiptManager::set_max_total_actions`], but is neither reported to the metrics and
+    /// callbacks nor returned by the accessors until it is filled. Unfilled placeholders are
+    /// rejected by [`ReceiptManager::validate_all`] and dropped when their receipt is taken, so
+    /// they are never sent.
+    ///
+    /// Fails with [`HostError::InvalidReceiptIndex`] if the receipt doesn't exist.
+    pub fn reserve_action_slot(&mut self, receipt_index: ReceiptIndex) -> logic::Result<usize> {
+        if receipt_index as usize >= self.action_receipts.len() {
+            return Err(HostError::InvalidReceiptIndex { receipt_index }.into());
+        }
+        self.check_total_actions()?;
+        let actions = &mut self.action_receipts[receipt_index as usize].1.actions;
+        actions.push(Action::CreateAccount(CreateAccountAction {}));
+        let action_index = actions.len() - 1;
+        self.action_slots
+            .push(FunctionCallActionIndex { receipt_index: receipt_index as usize, action_index });
+        Ok(action_index)
+    }
+
+    /// Replaces the placeholder reserved with [`ReceiptManager::reserve_action_slot`] at
+    /// `action_index` of the receipt `receipt_index` with `action`, which is then reported to
+    /// the metrics and callbacks like an appended action.
+    ///
+    /// `action` is checked like by the `append_action_*` method for its kind, e.g. against the
+    /// contract size limit or for duplicate access key nonces, and its deposit counts towards
+    /// the deposit limit.
+    ///
+    /// Fails with [`HostError::InvalidActionIndex`] if there is no unfilled placeholder at that
+    /// position, e.g. because it was already filled.
+    pub fn fill_action_slot(
+        &mut self,
+        receipt_index: ReceiptIndex,
+        action_index: usize,
+        action: Action,
+    ) -> logic::Result<()> {
+        let slot = FunctionCallActionIndex { receipt_index: receipt_index as usize, action_index };
+        let position = self
+            .action_slots
+            .iter()
+            .position(|reserved| *reserved == slot)
+            .ok_or(HostError::InvalidActionIndex { action_index: action_index as u64 })?;
+        self.check_action(&action)?;
+        self.attached_deposit = self.attached_deposit_with(&action)?;
+        self.action_slots.swap_remove(position);
+        self.action_receipts[slot.receipt_index].1.actions[action_index] = action;
+        self.action_added(receipt_index, action_index);
+        Ok(())
+    }
+
+    /// Whether the action at `action_index` of the receipt `receipt_index` is an unfilled
+    /// placeholder reserved with [`ReceiptManager::reserve_action_slot`].
+    fn is_action_slot(&self, receipt_index: usize, action_index: usize) -> bool {
+        self.action_slots.contains(&FunctionCallActionIndex { receipt_index, action_index })
+    }
+
+    /// Returns the actions of `receipt`, the receipt `receipt_index`, other than unfilled
+    /// placeholders, together with their indices.
+    fn filled_actions<'a>(
+        &'a self,
+        receipt_index: usize,
+        receipt: &'a ReceiptMetadata,
+    ) -> impl Iterator<Item = (usize, &'a Action)> + 'a {
+        receipt
+            .actions
+            .iter()
+            .enumerate()
+            .filter(move |(action_index, _)| !self.is_action_slot(receipt_index, *action_index))
+    }
+
+    /// Applies `remap` to the positions of the actions with gas weights and of the reserved
+    /// action slots, dropping those for which it returns `None`.
+    fn remap_action_indices(
+        &mut self,
+        mut remap: impl FnMut(FunctionCallActionIndex) -> Option<FunctionCallActionIndex>,
+    ) {
+        self.gas_weights.retain_mut(|entry| match remap(entry.index) {
+            Some(index) => {
+                entry.index = index;
+                true
+            }
+            None => false,
+        });
+        self.action_slots.retain_mut(|slot| match remap(*slot) {
+            Some(index) => {
+                *slot = index;
+                true
+            }
+            None => false,
+        });
+    }
+
+    /// Removes the unfilled placeholders reserved in `receipt`, the receipt `receipt_index`,
+    /// from its actions. The slots themselves are left for the caller to drop.
+    fn strip_action_slots(&self, receipt_index: usize, receipt: &mut ReceiptMetadata) {
+        let mut action_indices: Vec<usize> = self
+            .action_slots
+            .iter()
+            .filter(|slot| slot.receipt_index == receipt_index)
+            .map(|slot| slot.action_index)
+            .collect();
+        // Removing from the back keeps the indices of the other placeholders valid.
+        action_indices.sort_unstable();
+        for action_index in action_indices.into_iter().rev() {
+            receipt.actions.remove(action_index);
+        }
+    }
+
+    /// Reports the operations of this manager to `metrics`.
+    pub fn set_metrics(&mut self, metrics: Arc<dyn ReceiptManagerMetrics>) {
+        self.metrics = MetricsHook(Some(metrics));
+    }
+
+    /// Invokes `callback` with the index of a receipt whenever appending, inserting or filling
+    /// an action brings the receipt to `num_actions` actions, not counting unfilled action
+    /// slots. With `num_actions` of 1, the callback is invoked as soon as a receipt stops being
+    /// empty.
+    ///
+    /// Each receipt triggers the callback at most once, unless actions are removed from it.
+    pub fn set_on_receipt_ready(&mut self, num_actions: usize, callback: ReceiptReadyCallback) {
+        self.on_receipt_ready = ReceiptReadyHook(Some((num_actions, callback)));
+    }
+
+    /// Invokes `hook` with the estimated gas cost of every receipt created with
+    /// [`ReceiptManager::create_receipt`] and every action appended or inserted afterwards.
+    ///
+    /// Costs are the send fees of `fees`, assuming the receiver is another account: the action
+    /// receipt fee plus one data receipt fee per dependency for receipts, and the fee of the
+    /// action kind, including its per-byte part, for actions. Nothing is charged to the
+    /// receipts themselves.
+    pub fn set_construction_gas_hook(
+        &mut self,
+        fees: RuntimeFeesConfig,
+        hook: ConstructionGasCallback,
+    ) {
+        self.construction_gas = ConstructionGasHook(Some((Arc::new(fees), hook)));
+    }
+
+    /// Limits the total deposit that can be attached across all receipts of this manager.
+    ///
+    /// Appending a transfer or function call action which would bring the total above the
+    /// limit fails with [`HostError::BalanceExceeded`].
+    pub fn set_deposit_limit(&mut self, limit: Balance) {
+        self.deposit_limit = Some(limit);
+    }
+
+    /// Limits the size of the code attached to [`DeployContractAction`]s.
+    ///
+    /// Appending a deploy action with larger code fails with
+    /// [`HostError::ContractSizeExceeded`] instead of queueing a receipt which would fail on
+    /// execution.
+    pub fn set_max_contract_size(&mut self, limit: u64) {
+        self.max_contract_size = Some(limit);
+    }
+
+    /// Limits the number of actions across all receipts of this manager, capping the work a
+    /// single contract call can schedule.
+    ///
+    /// Appending or inserting an action beyond the limit fails with
+    /// [`HostError::NumberActionsExceeded`].
+    pub fn set_max_total_actions(&mut self, limit: u64) {
+        self.max_total_actions = Some(limit);
+    }
+
+    /// Sets a soft limit on the number of actions across all receipts of this manager.
+    ///
+    /// Unlike [`ReceiptManager::set_max_total_actions`], actions beyond the limit are still
+    /// accepted, but [`ReceiptManager::is_queue_full`] and
+    /// [`ReceiptManager::append_action_with_backpressure`] report that the caller should stop
+    /// scheduling more work.
+    pub fn set_soft_max_total_actions(&mut self, limit: u64) {
+        self.soft_max_total_actions = Some(limit);
+    }
+
+    /// Whether the number of actions across all receipts reached the soft limit set with
+    /// [`ReceiptManager::set_soft_max_total_actions`]. Always false without a soft limit.
+    pub fn is_queue_full(&self) -> bool {
+        self.soft_max_total_actions.map_or(false, |limit| self.total_action_count() as u64 >= limit)
+    }
+
+    /// Appends `action` to the receipt `receipt_index` like [`ReceiptManager::insert_action`]
+    /// at the last position, then returns whether the soft limit set with
+    /// [`ReceiptManager::set_soft_max_total_actions`] is reached. The action is stored either
+    /// way; only the hard limit set with [`ReceiptManager::set_max_total_actions`] rejects it.
+    pub fn append_action_with_backpressure(
+        &mut self,
+        receipt_index: ReceiptIndex,
+        action: Action,
+    ) -> logic::Result<bool> {
+        let position = self
+            .action_receipts
+            .get(receipt_index as usize)
+            .ok_or(HostError::InvalidReceiptIndex { receipt_index })?
+            .1
+            .actions
+            .len();
+        self.insert_action(receipt_index, position, action)?;
+        Ok(self.is_queue_full())
+    }
+
+    /// Makes adding an access key fail with [`HostError::DuplicateAccessKeyNonce`] if an access
+    /// key with the same public key and nonce was already added to any receipt of this manager.
+    ///
+    /// This is a best-effort guard against contracts accidentally reusing nonces, it doesn't
+    /// change how the receipts are executed.
+    pub fn set_reject_duplicate_key_nonces(&mut self, reject: bool) {
+        self.reject_duplicate_key_nonces = reject;
+    }
+
+    /// Makes appending a [`StakeAction`] with a zero amount fail with [`HostError::ZeroStake`],
+    /// as such an action unstakes everything and is more often a bug than intended.
+    ///
+    /// Unstaking is still possible by explicitly asking for it, see
+    /// [`ReceiptBuilder::unstake`].
+    pub fn set_reject_zero_stake(&mut self, reject: bool) {
+        self.reject_zero_stake = reject;
+    }
+
+    /// Makes adding a function call access key fail with [`HostError::DuplicateMethodName`]
+    /// if its method names, once decoded, contain duplicates. Repeated names don't grant
+    /// anything but make the key larger.
+    pub fn set_reject_duplicate_method_names(&mut self, reject: bool) {
+        self.reject_duplicate_method_names = reject;
+    }
+
+    /// Makes appending a [`DeleteAccountAction`] whose beneficiary is the system account
+    /// succeed, as it did before such actions were rejected with
+    /// [`HostError::InvalidAccountId`]. Only meant for old protocol versions, the system
+    /// account can't receive the funds.
+    pub fn set_allow_system_beneficiary(&mut self, allow: bool) {
+        self.allow_system_beneficiary = allow;
+    }
+
+    /// Makes [`ReceiptManager::take_receipts`] and the methods built on it move
+    /// [`CreateAccountAction`]s to the front and [`DeleteAccountAction`]s to the back of each
+    /// receipt, so that the order in which these actions were appended doesn't affect the
+    /// receipts. Other actions keep their relative order, which is meaningful.
+    pub fn set_sort_actions_on_take(&mut self, sort: bool) {
+        self.sort_actions_on_take = sort;
+    }
+
+    /// Makes creating a receipt fail with [`HostError::InvalidReceiverId`] if `forbidden`
+    /// returns true for its receiver, e.g. to keep contracts from targeting system accounts.
+    pub fn set_forbidden_receiver(&mut self, forbidden: fn(&AccountId) -> bool) {
+        self.forbidden_receiver = ForbiddenReceiver(Some(forbidden));
+    }
+
+    /// Fails with [`HostError::DuplicateAccessKeyNonce`] if nonce checks are enabled and an
+    /// access key for `public_key` with `nonce` was already added.
+    fn check_key_nonce(&self, public_key: &PublicKey, nonce: Nonce) -> logic::Result<()> {
+        if !self.reject_duplicate_key_nonces {
+            return Ok(());
+        }
+        let reused =
+            self.action_receipts.iter().flat_map(|(_, receipt)| &receipt.actions).any(|action| {
+                matches!(action, Action::AddKey(AddKeyAction { public_key: key, access_key })
+                    if key == public_key && access_key.nonce == nonce)
+            });
+        if reused {
+            return Err(HostError::DuplicateAccessKeyNonce { nonce }.into());
+        }
+        Ok(())
+    }
+
+    /// Runs the checks of the `append_action_*` method for the kind of `action`, except for the
+    /// deposit limit, which is checked by [`ReceiptManager::attached_deposit_with`].
+    fn check_action(&self, action: &Action) -> logic::Result<()> {
+        match action {
+            Action::DeployContract(DeployContractAction { code }) => self.check_contract_size(code),
+            Action::Stake(StakeAction { stake: 0, .. }) if self.reject_zero_stake => {
+                Err(HostError::ZeroStake.into())
+            }
+            Action::AddKey(AddKeyAction { public_key, access_key }) => {
+                if let AccessKeyPermission::FunctionCall(permission) = &access_key.permission {
+                    if permission.allowance == Some(0) {
+                        return Err(HostError::ZeroAllowance.into());
+                    }
+                    self.check_method_names(&permission.method_names)?;
+                }
+                self.check_key_nonce(public_key, access_key.nonce)
+            }
+            Action::DeleteAccount(DeleteAccountAction { beneficiary_id }) => {
+                self.check_beneficiary(beneficiary_id)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Fails with [`HostError::ContractSizeExceeded`] if `code` is larger than the limit set
+    /// with [`ReceiptManager::set_max_contract_size`].
+    fn check_contract_size(&self, code: &[u8]) -> logic::Result<()> {
+        if let Some(limit) = self.max_contract_size {
+            let size = code.len() as u64;
+            if size > limit {
+                return Err(HostError::ContractSizeExceeded { size, limit }.into());
+            }
+        }
+        Ok(())
+    }
+
+    /// Fails with [`HostError::DuplicateMethodName`] if duplicate method names are rejected and
+    /// `method_names` contains duplicates.
+    fn check_method_names(&self, method_names: &[String]) -> logic::Result<()> {
+        if self.reject_duplicate_method_names {
+            for (index, method_name) in method_names.iter().enumerate() {
+                if method_names[..index].contains(method_name) {
+                    return Err(HostError::DuplicateMethodName {
+                        method_name: method_name.clone(),
+                    }
+                    .into());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Fails with [`HostError::InvalidAccountId`] if `beneficiary_id` is the system account,
+    /// unless allowed with [`ReceiptManager::set_allow_system_beneficiary`].
+    fn check_beneficiary(&self, beneficiary_id: &AccountId) -> logic::Result<()> {
+        if !self.allow_system_beneficiary && beneficiary_id.is_system() {
+            return Err(HostError::InvalidAccountId.into());
+        }
+        Ok(())
+    }
+
+    /// Returns the total attached deposit with the deposit of `action` added, see
+    /// [`ReceiptManager::checked_attached_deposit`].
+    fn attached_deposit_with(&self, action: &Action) -> logic::Result<Balance> {
+        match action {
+            Action::Transfer(TransferAction { deposit })
+            | Action::FunctionCall(FunctionCallAction { deposit, .. }) => {
+                self.checked_attached_deposit(*deposit)
+            }
+            _ => Ok(self.attached_deposit),
+        }
+    }
+
+    /// Returns the total attached deposit with `deposit` added, failing with
+    /// [`HostError::BalanceExceeded`] if the total overflows or exceeds the deposit limit.
+    ///
+    /// The total is only stored by the caller once the action is added, so that failing to add
+    /// it doesn't leave the deposit accounted for.
+    fn checked_attached_deposit(&self, deposit: Balance) -> logic::Result<Balance> {
+        self.attached_deposit
+            .checked_add(deposit)
+            .filter(|total| self.deposit_limit.map_or(true, |limit| *total <= limit))
+            .ok_or_else(|| HostError::BalanceExceeded.into())
+    }
+
+    /// Whether `data_id` is already used by an output data receiver of some receipt.
+    fn is_data_id_used(&self, data_id: &CryptoHash) -> bool {
+        self.receipt_index_for_data_id(data_id).is_some()
     }
 
     /// Create a receipt which will be executed after all the receipts identified by
@@ -93,20 +1092,33 @@ impl ReceiptManager {
     /// If any of the [`RecepitIndex`]es do not refer to a known receipt, this function will fail
     /// with an error.
     ///
+    /// If `ext` generates a data id which is already used by an output data receiver of some
+    /// receipt, this function fails with [`HostError::DuplicateDataId`], as the dependency graph
+    /// would be corrupted otherwise.
+    ///
+    /// If the receiver is forbidden by [`ReceiptManager::set_forbidden_receiver`], this function
+    /// fails with [`HostError::InvalidReceiverId`].
+    ///
     /// # Arguments
     ///
     /// * `generate_data_id` - function to generate a data id to connect receipt output to
     /// * `receipt_indices` - a list of receipt indices the new receipt is depend on
     /// * `receiver_id` - account id of the receiver of the receipt created
-    pub(crate) fn create_receipt(
+    pub fn create_receipt(
         &mut self,
         ext: &mut dyn External,
         receipt_indices: Vec<ReceiptIndex>,
         receiver_id: AccountId,
     ) -> logic::Result<ReceiptIndex> {
+        if self.forbidden_receiver.forbids(&receiver_id) {
+            return Err(HostError::InvalidReceiverId { account_id: receiver_id.into() }.into());
+        }
         let mut input_data_ids = vec![];
         for receipt_index in receipt_indices {
             let data_id = ext.generate_data_id();
+            if self.is_data_id_used(&data_id) {
+                return Err(HostError::DuplicateDataId.into());
+            }
             self.action_receipts
                 .get_mut(receipt_index as usize)
                 .ok_or_else(|| HostError::InvalidReceiptIndex { receipt_index })?
@@ -116,11 +1128,224 @@ impl ReceiptManager {
             input_data_ids.push(data_id);
         }
 
-        let new_receipt =
-            ReceiptMetadata { output_data_receivers: vec![], input_data_ids, actions: vec![] };
-        let new_receipt_index = self.action_receipts.len() as ReceiptIndex;
-        self.action_receipts.push((receiver_id, new_receipt));
-        Ok(new_receipt_index)
+        let new_receipt = ReceiptMetadata {
+            output_data_receivers: vec![],
+            input_data_ids,
+            actions: vec![],
+            gas_price: None,
+        };
+        let num_dependencies = new_receipt.input_data_ids.len();
+        let new_receipt_index = self.action_receipts.len() as ReceiptIndex;
+        self.action_receipts.push((receiver_id, new_receipt));
+        self.metrics.with(|metrics| metrics.receipt_created(num_dependencies));
+        if let Some((fees, hook)) = &self.construction_gas.0 {
+            let data_receipts_fee = fees
+                .data_receipt_creation_config
+                .base_cost
+                .send_fee(false)
+                .saturating_mul(num_dependencies as u64);
+            hook(
+                fees.action_receipt_creation_config
+                    .send_fee(false)
+                    .saturating_add(data_receipts_fee),
+            );
+        }
+        Ok(new_receipt_index)
+    }
+
+    /// Checks the invariants of all pending receipts in one pass and returns the first
+    /// violation.
+    ///
+    /// The appending methods already enforce most of these, but receipts can be modified after
+    /// the fact, e.g. when limits are configured later. The checks are:
+    ///
+    /// * code of deploy actions fits the limit set with
+    ///   [`ReceiptManager::set_max_contract_size`] ([`HostError::ContractSizeExceeded`]),
+    /// * function call access keys don't have a zero allowance ([`HostError::ZeroAllowance`]),
+    /// * delete account actions don't transfer the funds to the system account unless allowed
+    ///   with [`ReceiptManager::set_allow_system_beneficiary`] ([`HostError::InvalidAccountId`]),
+    /// * the gas prepaid for the function calls of a receipt doesn't overflow
+    ///   ([`HostError::IntegerOverflow`]),
+    /// * the total attached deposit doesn't overflow nor exceed the limit set with
+    ///   [`ReceiptManager::set_deposit_limit`] ([`HostError::BalanceExceeded`]),
+    /// * the number of actions across all receipts doesn't exceed the limit set with
+    ///   [`ReceiptManager::set_max_total_actions`] ([`HostError::NumberActionsExceeded`]),
+    /// * all action slots are filled, see [`ReceiptManager::reserve_action_slot`]
+    ///   ([`HostError::InvalidActionIndex`]),
+    /// * there are no dependency cycles, see [`ReceiptManager::check_dependency_cycles`].
+    ///
+    /// Receipts without actions are valid and receiver ids are valid by construction.
+    pub fn validate_all(&self) -> logic::Result<()> {
+        if let Some(slot) = self.action_slots.first() {
+            return Err(
+                HostError::InvalidActionIndex { action_index: slot.action_index as u64 }.into()
+            );
+        }
+        if let Some(limit) = self.max_total_actions {
+            if self.total_action_count() as u64 > limit {
+                return Err(HostError::NumberActionsExceeded { limit }.into());
+            }
+        }
+        let mut total_deposit: Balance = 0;
+        for (_, receipt) in &self.action_receipts {
+            let mut prepaid_gas: Gas = 0;
+            for action in &receipt.actions {
+                let deposit = match action {
+                    Action::DeployContract(DeployContractAction { code }) => {
+                        if let Some(limit) = self.max_contract_size {
+                            let size = code.len() as u64;
+                            if size > limit {
+                                return Err(HostError::ContractSizeExceeded { size, limit }.into());
+                            }
+                        }
+                        0
+                    }
+                    Action::AddKey(AddKeyAction { access_key, .. }) => {
+                        if let AccessKeyPermission::FunctionCall(FunctionCallPermission {
+                            allowance: Some(0),
+                            ..
+                        }) = access_key.permission
+                        {
+                            return Err(HostError::ZeroAllowance.into());
+                        }
+                        0
+                    }
+                    Action::FunctionCall(FunctionCallAction { gas, deposit, .. }) => {
+                        prepaid_gas =
+                            prepaid_gas.checked_add(*gas).ok_or(HostError::IntegerOverflow)?;
+                        *deposit
+                    }
+                    Action::Transfer(TransferAction { deposit }) => *deposit,
+                    Action::DeleteAccount(DeleteAccountAction { beneficiary_id })
+                        if !self.allow_system_beneficiary && beneficiary_id.is_system() =>
+                    {
+                        return Err(HostError::InvalidAccountId.into());
+                    }
+                    _ => 0,
+                };
+                total_deposit = total_deposit
+                    .checked_add(deposit)
+                    .filter(|total| self.deposit_limit.map_or(true, |limit| *total <= limit))
+                    .ok_or(HostError::BalanceExceeded)?;
+            }
+        }
+        self.check_dependency_cycles()
+    }
+
+    /// Checks that no receipt transitively depends on its own output data.
+    ///
+    /// Receipts are connected through the `data_id`s of their `output_data_receivers` and the
+    /// `input_data_ids` of the receipts consuming that data. [`ReceiptManager::create_receipt`]
+    /// only lets new receipts depend on existing ones, but the dependencies can still be
+    /// rewired afterwards, so this walks the dependency graph and fails with
+    /// [`HostError::ReceiptDependencyCycle`] on the first receipt found on a cycle.
+    pub fn check_dependency_cycles(&self) -> logic::Result<()> {
+        let graph = self.dependency_graph();
+        let dependents = |index: usize| graph[index].iter().copied();
+
+        #[derive(Clone, Copy, PartialEq)]
+        enum Visit {
+            NotVisited,
+            InProgress,
+            Done,
+        }
+        let mut visits = vec![Visit::NotVisited; self.action_receipts.len()];
+        for start in 0..self.action_receipts.len() {
+            if visits[start] != Visit::NotVisited {
+                continue;
+            }
+            visits[start] = Visit::InProgress;
+            let mut stack = vec![(start, dependents(start))];
+            while let Some((index, next)) = stack.last_mut() {
+                match next.next() {
+                    Some(dependent) => match visits[dependent] {
+                        Visit::NotVisited => {
+                            visits[dependent] = Visit::InProgress;
+                            stack.push((dependent, dependents(dependent)));
+                        }
+                        Visit::InProgress => {
+                            return Err(HostError::ReceiptDependencyCycle {
+                                receipt_index: dependent as ReceiptIndex,
+                            }
+                            .into());
+                        }
+                        Visit::Done => {}
+                    },
+                    None => {
+                        visits[*index] = Visit::Done;
+                        stack.pop();
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the indices of the receipts waiting for output data of the receipt
+    /// `receipt_index`, in increasing order. Returns an empty list if there is no receipt with
+    /// the given index.
+    pub fn dependents_of(&self, receipt_index: ReceiptIndex) -> Vec<ReceiptIndex> {
+        let output_data_ids: Vec<&CryptoHash> = match self.output_data_receivers(receipt_index) {
+            Some(receivers) => receivers.iter().map(|receiver| &receiver.data_id).collect(),
+            None => return vec![],
+        };
+        self.action_receipts
+            .iter()
+            .enumerate()
+            .filter(|(_, (_, receipt))| {
+                receipt.input_data_ids.iter().any(|data_id| output_data_ids.contains(&data_id))
+            })
+            .map(|(index, _)| index as ReceiptIndex)
+            .collect()
+    }
+
+    /// Returns the number of receipts on the longest chain of receipts waiting for the output
+    /// data of the previous one, or 0 if there are no receipts.
+    ///
+    /// Fails with [`HostError::ReceiptDependencyCycle`] if the dependencies form a cycle, see
+    /// [`ReceiptManager::check_dependency_cycles`].
+    pub fn max_dependency_depth(&self) -> logic::Result<usize> {
+        self.check_dependency_cycles()?;
+        let graph = self.dependency_graph();
+        let mut pending_inputs = vec![0usize; graph.len()];
+        for dependent in graph.iter().flatten() {
+            pending_inputs[*dependent] += 1;
+        }
+        // Visit the receipts in topological order, so that the depth of a receipt is final
+        // once all the receipts it depends on are visited.
+        let mut depths = vec![1; graph.len()];
+        let mut ready: Vec<usize> = (0..graph.len()).filter(|&i| pending_inputs[i] == 0).collect();
+        while let Some(index) = ready.pop() {
+            for &dependent in &graph[index] {
+                depths[dependent] = depths[dependent].max(depths[index] + 1);
+                pending_inputs[dependent] -= 1;
+                if pending_inputs[dependent] == 0 {
+                    ready.push(dependent);
+                }
+            }
+        }
+        Ok(depths.into_iter().max().unwrap_or(0))
+    }
+
+    /// Returns, for every receipt, the indices of the receipts consuming its output data.
+    fn dependency_graph(&self) -> Vec<Vec<usize>> {
+        let mut consumers: HashMap<&CryptoHash, Vec<usize>> = HashMap::new();
+        for (index, (_, receipt)) in self.action_receipts.iter().enumerate() {
+            for data_id in &receipt.input_data_ids {
+                consumers.entry(data_id).or_default().push(index);
+            }
+        }
+        self.action_receipts
+            .iter()
+            .map(|(_, receipt)| {
+                receipt
+                    .output_data_receivers
+                    .iter()
+                    .flat_map(|receiver| consumers.get(&receiver.data_id).into_iter().flatten())
+                    .copied()
+                    .collect()
+            })
+            .collect()
     }
 
     /// Attach the [`CreateAccountAction`] action to an existing receipt.
@@ -132,11 +1357,11 @@ impl ReceiptManager {
     /// # Panics
     ///
     /// Panics if the `receipt_index` does not refer to a known receipt.
-    pub(crate) fn append_action_create_account(
+    pub fn append_action_create_account(
         &mut self,
         receipt_index: ReceiptIndex,
     ) -> logic::Result<()> {
-        self.append_action(receipt_index, Action::CreateAccount(CreateAccountAction {}));
+        self.append_action(receipt_index, Action::CreateAccount(CreateAccountAction {}))?;
         Ok(())
     }
 
@@ -147,15 +1372,19 @@ impl ReceiptManager {
     /// * `receipt_index` - an index of Receipt to append an action
     /// * `code` - a Wasm code to attach
     ///
+    /// Fails with [`HostError::ContractSizeExceeded`] if the code is larger than the limit set
+    /// with [`ReceiptManager::set_max_contract_size`].
+    ///
     /// # Panics
     ///
     /// Panics if the `receipt_index` does not refer to a known receipt.
-    pub(crate) fn append_action_deploy_contract(
+    pub fn append_action_deploy_contract(
         &mut self,
         receipt_index: ReceiptIndex,
         code: Vec<u8>,
     ) -> logic::Result<()> {
-        self.append_action(receipt_index, Action::DeployContract(DeployContractAction { code }));
+        self.check_contract_size(&code)?;
+        self.append_action(receipt_index, Action::DeployContract(DeployContractAction { code }))?;
         Ok(())
     }
 
@@ -178,10 +1407,13 @@ impl ReceiptManager {
     /// * `prepaid_gas` - amount of prepaid gas to attach to the call
     /// * `gas_weight` - relative weight of unused gas to distribute to the function call action
     ///
+    /// Fails with [`HostError::BalanceExceeded`] if the total attached deposit would overflow
+    /// or exceed the deposit limit.
+    ///
     /// # Panics
     ///
     /// Panics if the `receipt_index` does not refer to a known receipt.
-    pub(crate) fn append_action_function_call_weight(
+    pub fn append_action_function_call_weight(
         &mut self,
         receipt_index: ReceiptIndex,
         method_name: Vec<u8>,
@@ -190,22 +1422,91 @@ impl ReceiptManager {
         prepaid_gas: Gas,
         gas_weight: GasWeight,
     ) -> logic::Result<()> {
+        let method_name = parse_method_name(method_name)?;
+        self.append_action_function_call_str(
+            receipt_index,
+            method_name,
+            args,
+            attached_deposit,
+            prepaid_gas,
+            gas_weight,
+            false,
+        )
+    }
+
+    /// Attach a [`FunctionCallAction`] calling `method_name` with the borsh-serialized `args`
+    /// to an existing receipt, sparing callers with structured arguments the serialization.
+    ///
+    /// The action doesn't take part in the distribution of unused gas.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the `receipt_index` does not refer to a known receipt.
+    pub fn append_action_function_call_typed<T: BorshSerialize>(
+        &mut self,
+        receipt_index: ReceiptIndex,
+        method_name: &str,
+        args: &T,
+        attached_deposit: Balance,
+        prepaid_gas: Gas,
+    ) -> logic::Result<()> {
+        let args = args.try_to_vec().expect("borsh serialization into a vec cannot fail");
+        self.append_action_function_call_str(
+            receipt_index,
+            method_name.to_string(),
+            args,
+            attached_deposit,
+            prepaid_gas,
+            GasWeight(0),
+            false,
+        )
+    }
+
+    /// Same as [`append_action_function_call_weight`](Self::append_action_function_call_weight)
+    /// for a method name which is already a `String`, skipping the UTF-8 validation.
+    ///
+    /// With `participate_with_floor`, the action takes part in the distribution of unused gas
+    /// even if `gas_weight` is zero, with a weight of at least 1. Its prepaid gas then becomes
+    /// a guaranteed minimum instead of a base: the action ends up with its share of the unused
+    /// gas or its prepaid gas, whichever is larger.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the `receipt_index` does not refer to a known receipt.
+    #[allow(clippy::too_many_arguments)]
+    pub fn append_action_function_call_str(
+        &mut self,
+        receipt_index: ReceiptIndex,
+        method_name: String,
+        args: Vec<u8>,
+        attached_deposit: Balance,
+        prepaid_gas: Gas,
+        gas_weight: GasWeight,
+        participate_with_floor: bool,
+    ) -> logic::Result<()> {
+        let total_attached_deposit = self.checked_attached_deposit(attached_deposit)?;
         let action_index = self.append_action(
             receipt_index,
             Action::FunctionCall(FunctionCallAction {
-                method_name: String::from_utf8(method_name)
-                    .map_err(|_| HostError::InvalidMethodName)?,
+                method_name,
                 args,
                 gas: prepaid_gas,
                 deposit: attached_deposit,
             }),
-        );
+        )?;
+        self.attached_deposit = total_attached_deposit;
 
-        if gas_weight.0 > 0 {
-            self.gas_weights.push((
-                FunctionCallActionIndex { receipt_index: receipt_index as usize, action_index },
-                gas_weight,
-            ));
+        if gas_weight.0 > 0 || participate_with_floor {
+            self.gas_weights.push(GasWeightEntry {
+                index: FunctionCallActionIndex {
+                    receipt_index: receipt_index as usize,
+                    action_index,
+                },
+                weight: GasWeight(gas_weight.0.max(1)),
+                max_gas_per_action: None,
+                min_gas_per_action: None,
+                prepaid_gas_is_floor: participate_with_floor,
+            });
         }
 
         Ok(())
@@ -218,15 +1519,20 @@ impl ReceiptManager {
     /// * `receipt_index` - an index of Receipt to append an action
     /// * `amount` - amount of tokens to transfer
     ///
+    /// Fails with [`HostError::BalanceExceeded`] if the total attached deposit would overflow
+    /// or exceed the deposit limit.
+    ///
     /// # Panics
     ///
     /// Panics if the `receipt_index` does not refer to a known receipt.
-    pub(crate) fn append_action_transfer(
+    pub fn append_action_transfer(
         &mut self,
         receipt_index: ReceiptIndex,
         deposit: Balance,
     ) -> logic::Result<()> {
-        self.append_action(receipt_index, Action::Transfer(TransferAction { deposit }));
+        let attached_deposit = self.checked_attached_deposit(deposit)?;
+        self.append_action(receipt_index, Action::Transfer(TransferAction { deposit }))?;
+        self.attached_deposit = attached_deposit;
         Ok(())
     }
 
@@ -238,10 +1544,40 @@ impl ReceiptManager {
     /// * `stake` - amount of tokens to stake
     /// * `public_key` - a validator public key
     ///
+    /// A zero `stake` fails with [`HostError::ZeroStake`] if enabled with
+    /// [`ReceiptManager::set_reject_zero_stake`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the `receipt_index` does not refer to a known receipt.
+    pub fn append_action_stake(
+        &mut self,
+        receipt_index: ReceiptIndex,
+        stake: Balance,
+        public_key: Vec<u8>,
+    ) -> logic::Result<()> {
+        if stake == 0 && self.reject_zero_stake {
+            return Err(HostError::ZeroStake.into());
+        }
+        self.append_stake(receipt_index, stake, public_key)
+    }
+
+    /// Attach a [`StakeAction`] with a zero amount, unstaking the validator key, to an existing
+    /// receipt. Unlike [`ReceiptManager::append_action_stake`], this is never rejected as an
+    /// accidental zero stake.
+    ///
     /// # Panics
     ///
     /// Panics if the `receipt_index` does not refer to a known receipt.
-    pub(crate) fn append_action_stake(
+    pub fn append_action_unstake(
+        &mut self,
+        receipt_index: ReceiptIndex,
+        public_key: Vec<u8>,
+    ) -> logic::Result<()> {
+        self.append_stake(receipt_index, 0, public_key)
+    }
+
+    fn append_stake(
         &mut self,
         receipt_index: ReceiptIndex,
         stake: Balance,
@@ -249,12 +1585,8 @@ impl ReceiptManager {
     ) -> logic::Result<()> {
         self.append_action(
             receipt_index,
-            Action::Stake(StakeAction {
-                stake,
-                public_key: PublicKey::try_from_slice(&public_key)
-                    .map_err(|_| HostError::InvalidPublicKey)?,
-            }),
-        );
+            Action::Stake(StakeAction { stake, public_key: parse_public_key(&public_key)? }),
+        )?;
         Ok(())
     }
 
@@ -269,20 +1601,21 @@ impl ReceiptManager {
     /// # Panics
     ///
     /// Panics if the `receipt_index` does not refer to a known receipt.
-    pub(crate) fn append_action_add_key_with_full_access(
+    pub fn append_action_add_key_with_full_access(
         &mut self,
         receipt_index: ReceiptIndex,
         public_key: Vec<u8>,
         nonce: Nonce,
     ) -> logic::Result<()> {
+        let public_key = parse_public_key(&public_key)?;
+        self.check_key_nonce(&public_key, nonce)?;
         self.append_action(
             receipt_index,
             Action::AddKey(AddKeyAction {
-                public_key: PublicKey::try_from_slice(&public_key)
-                    .map_err(|_| HostError::InvalidPublicKey)?,
+                public_key,
                 access_key: AccessKey { nonce, permission: AccessKeyPermission::FullAccess },
             }),
-        );
+        )?;
         Ok(())
     }
 
@@ -296,14 +1629,19 @@ impl ReceiptManager {
     /// * `receipt_index` - an index of Receipt to append an action
     /// * `public_key` - a public key for an access key
     /// * `nonce` - a nonce
-    /// * `allowance` - amount of tokens allowed to spend by this access key
+    /// * `allowance` - amount of tokens allowed to spend by this access key (`None` = unlimited)
     /// * `receiver_id` - a contract witch will be allowed to call with this access key
     /// * `method_names` - a list of method names is allowed to call with this access key (empty = any method)
     ///
+    /// An allowance of `Some(0)` is disallowed and fails with [`HostError::ZeroAllowance`], as
+    /// such a key could never pay for a call. Duplicate method names fail with
+    /// [`HostError::DuplicateMethodName`] if enabled with
+    /// [`ReceiptManager::set_reject_duplicate_method_names`].
+    ///
     /// # Panics
     ///
     /// Panics if the `receipt_index` does not refer to a known receipt.
-    pub(crate) fn append_action_add_key_with_function_call(
+    pub fn append_action_add_key_with_function_call(
         &mut self,
         receipt_index: ReceiptIndex,
         public_key: Vec<u8>,
@@ -312,27 +1650,28 @@ impl ReceiptManager {
         receiver_id: AccountId,
         method_names: Vec<Vec<u8>>,
     ) -> logic::Result<()> {
+        if allowance == Some(0) {
+            return Err(HostError::ZeroAllowance.into());
+        }
+        let public_key = parse_public_key(&public_key)?;
+        self.check_key_nonce(&public_key, nonce)?;
+        let method_names =
+            method_names.into_iter().map(parse_method_name).collect::<logic::Result<Vec<_>>>()?;
+        self.check_method_names(&method_names)?;
         self.append_action(
             receipt_index,
             Action::AddKey(AddKeyAction {
-                public_key: PublicKey::try_from_slice(&public_key)
-                    .map_err(|_| HostError::InvalidPublicKey)?,
+                public_key,
                 access_key: AccessKey {
                     nonce,
                     permission: AccessKeyPermission::FunctionCall(FunctionCallPermission {
                         allowance,
                         receiver_id: receiver_id.into(),
-                        method_names: method_names
-                            .into_iter()
-                            .map(|method_name| {
-                                String::from_utf8(method_name)
-                                    .map_err(|_| HostError::InvalidMethodName)
-                            })
-                            .collect::<std::result::Result<Vec<_>, _>>()?,
+                        method_names,
                     }),
                 },
             }),
-        );
+        )?;
         Ok(())
     }
 
@@ -346,18 +1685,15 @@ impl ReceiptManager {
     /// # Panics
     ///
     /// Panics if the `receipt_index` does not refer to a known receipt.
-    pub(crate) fn append_action_delete_key(
+    pub fn append_action_delete_key(
         &mut self,
         receipt_index: ReceiptIndex,
         public_key: Vec<u8>,
     ) -> logic::Result<()> {
         self.append_action(
             receipt_index,
-            Action::DeleteKey(DeleteKeyAction {
-                public_key: PublicKey::try_from_slice(&public_key)
-                    .map_err(|_| HostError::InvalidPublicKey)?,
-            }),
-        );
+            Action::DeleteKey(DeleteKeyAction { public_key: parse_public_key(&public_key)? }),
+        )?;
         Ok(())
     }
 
@@ -368,21 +1704,90 @@ impl ReceiptManager {
     /// * `receipt_index` - an index of Receipt to append an action
     /// * `beneficiary_id` - an account id to which the rest of the funds of the removed account will be transferred
     ///
+    /// Fails with [`HostError::InvalidAccountId`] if `beneficiary_id` is the system account,
+    /// which can't receive the funds, unless allowed with
+    /// [`ReceiptManager::set_allow_system_beneficiary`].
+    ///
     /// # Panics
     ///
     /// Panics if the `receipt_index` does not refer to a known receipt.
-    pub(crate) fn append_action_delete_account(
+    pub fn append_action_delete_account(
         &mut self,
         receipt_index: ReceiptIndex,
         beneficiary_id: AccountId,
     ) -> logic::Result<()> {
+        self.check_beneficiary(&beneficiary_id)?;
         self.append_action(
             receipt_index,
             Action::DeleteAccount(DeleteAccountAction { beneficiary_id }),
-        );
+        )?;
         Ok(())
     }
 
+    /// Appends the action described by `spec` to the receipt `receipt_index` with the
+    /// corresponding `append_action_*` method and returns the index of the action.
+    ///
+    /// Fails with [`HostError::InvalidReceiptIndex`] if the receipt doesn't exist and with the
+    /// errors of the `append_action_*` method otherwise.
+    pub fn append_action_from_spec(
+        &mut self,
+        receipt_index: ReceiptIndex,
+        spec: ActionSpec,
+    ) -> logic::Result<usize> {
+        if receipt_index as usize >= self.action_receipts.len() {
+            return Err(HostError::InvalidReceiptIndex { receipt_index }.into());
+        }
+        match spec {
+            ActionSpec::CreateAccount => self.append_action_create_account(receipt_index),
+            ActionSpec::DeployContract { code } => {
+                self.append_action_deploy_contract(receipt_index, code)
+            }
+            ActionSpec::FunctionCall {
+                method_name,
+                args,
+                attached_deposit,
+                prepaid_gas,
+                gas_weight,
+            } => self.append_action_function_call_str(
+                receipt_index,
+                method_name,
+                args,
+                attached_deposit,
+                prepaid_gas,
+                GasWeight(gas_weight),
+                false,
+            ),
+            ActionSpec::Transfer { deposit } => self.append_action_transfer(receipt_index, deposit),
+            ActionSpec::Stake { stake, public_key } => {
+                self.append_action_stake(receipt_index, stake, public_key)
+            }
+            ActionSpec::AddKeyWithFullAccess { public_key, nonce } => {
+                self.append_action_add_key_with_full_access(receipt_index, public_key, nonce)
+            }
+            ActionSpec::AddKeyWithFunctionCall {
+                public_key,
+                nonce,
+                allowance,
+                receiver_id,
+                method_names,
+            } => self.append_action_add_key_with_function_call(
+                receipt_index,
+                public_key,
+                nonce,
+                allowance,
+                receiver_id,
+                method_names.into_iter().map(String::into_bytes).collect(),
+            ),
+            ActionSpec::DeleteKey { public_key } => {
+                self.append_action_delete_key(receipt_index, public_key)
+            }
+            ActionSpec::DeleteAccount { beneficiary_id } => {
+                self.append_action_delete_account(receipt_index, beneficiary_id)
+            }
+        }?;
+        Ok(self.action_receipts[receipt_index as usize].1.actions.len() - 1)
+    }
+
     /// Distribute the gas among the scheduled function calls that specify a gas weight.
     ///
     /// Distributes the gas passed in by splitting it among weights defined in `gas_weights`.
@@ -390,6 +1795,21 @@ impl ReceiptManager {
     /// to add the respective amount of gas. Once all gas is distributed, the remainder of
     /// the gas not assigned due to precision loss is added to the last function with a weight.
     ///
+    /// Functions whose share would exceed their [`ReceiptManager::set_max_gas_per_action`]
+    /// ceiling receive exactly the ceiling, and the rest of the gas is split again among the
+    /// other functions by their weights. Gas which doesn't fit under any ceiling stays unused
+    /// and is reported with [`GasDistribution::Partial`].
+    ///
+    /// Functions appended with `participate_with_floor` only receive the part of their share
+    /// exceeding their prepaid gas; the share which doesn't lift them above it stays unused
+    /// and is reported with [`GasDistribution::Partial`] as well.
+    ///
+    /// Functions with a [`ReceiptManager::set_min_gas_per_action`] minimum receive it before
+    /// the rest of the gas is split by weight.
+    ///
+    /// The gas weights are cleared afterwards, so gas is distributed at most once, see
+    /// [`ReceiptManager::distribute_unused_gas_keep_weights`] to keep them.
+    ///
     /// # Arguments
     ///
     /// * `gas` - amount of unused gas to distribute
@@ -397,39 +1817,718 @@ impl ReceiptManager {
     /// # Returns
     ///
     /// Function returns a [GasDistribution] that indicates how the gas was distributed.
-    pub(crate) fn distribute_unused_gas(&mut self, unused_gas: Gas) -> GasDistribution {
+    /// [GasDistribution::Saturated] is returned if the gas of some function call had to be
+    /// clamped to `Gas::MAX`. [GasDistribution::MinGasNotCovered] is returned instead if the
+    /// gas didn't cover all minimums and was split in proportion to them.
+    /// [GasDistribution::Partial] takes precedence over both if some of the gas stays unused.
+    pub fn distribute_unused_gas(&mut self, unused_gas: Gas) -> GasDistribution {
         let gas_weight_sum: u128 =
-            self.gas_weights.iter().map(|(_, GasWeight(weight))| *weight as u128).sum();
+            self.gas_weights.iter().map(|entry| entry.weight.0 as u128).sum();
 
         if gas_weight_sum == 0 {
             return GasDistribution::NoRatios;
         }
+        self.metrics
+            .with(|metrics| metrics.unused_gas_distributed(unused_gas, self.gas_weights.len()));
 
-        let mut distribute_gas = |index: &FunctionCallActionIndex, assigned_gas: Gas| {
+        let min_gas_covered = min_gas_covered(&self.gas_weights, unused_gas);
+        let gas_to_add = self.gas_to_add(unused_gas);
+        // Each share is a part of `unused_gas`, so their sum cannot exceed it.
+        let undistributed = unused_gas - gas_to_add.iter().sum::<Gas>();
+        let mut saturated = false;
+        for (entry, assigned_gas) in self.gas_weights.iter().zip(gas_to_add) {
             let FunctionCallAction { gas, .. } =
-                get_fuction_call_action_mut(&mut self.action_receipts, *index);
+                get_fuction_call_action_mut(&mut self.action_receipts, entry.index);
 
-            // Operation cannot overflow because the amount of assigned gas is a fraction of
-            // the unused gas and is using floor division.
-            *gas += assigned_gas;
-        };
+            // The amount of assigned gas is a fraction of the unused gas, but the prepaid gas
+            // of the function call can still be large enough for the sum to overflow.
+            *gas = gas.checked_add(assigned_gas).unwrap_or_else(|| {
+                saturated = true;
+                Gas::MAX
+            });
+        }
+        self.gas_weights.clear();
+        if undistributed > 0 {
+            GasDistribution::Partial { undistributed }
+        } else if !min_gas_covered {
+            GasDistribution::MinGasNotCovered
+        } else if saturated {
+            GasDistribution::Saturated
+        } else {
+            GasDistribution::All
+        }
+    }
+
+    /// Same as [`ReceiptManager::distribute_unused_gas`], but keeps the gas weights so that
+    /// what was distributed can be inspected afterwards, e.g. with
+    /// [`ReceiptManager::pending_gas_weights`]. Meant for debugging: distributing again would
+    /// add gas to the same actions a second time.
+    pub fn distribute_unused_gas_keep_weights(&mut self, unused_gas: Gas) -> GasDistribution {
+        let gas_weights = self.gas_weights.clone();
+        let distribution = self.distribute_unused_gas(unused_gas);
+        self.gas_weights = gas_weights;
+        distribution
+    }
+
+    /// Same as [`ReceiptManager::distribute_unused_gas`], but only among the function call
+    /// actions of the receipt `receipt_index`. Gas weights of the other receipts are kept for a
+    /// later distribution.
+    pub fn distribute_unused_gas_subset(
+        &mut self,
+        unused_gas: Gas,
+        receipt_index: ReceiptIndex,
+    ) -> GasDistribution {
+        let (subset, others): (Vec<_>, Vec<_>) = std::mem::take(&mut self.gas_weights)
+            .into_iter()
+            .partition(|entry| entry.index.receipt_index == receipt_index as usize);
+        self.gas_weights = subset;
+        let distribution = self.distribute_unused_gas(unused_gas);
+        self.gas_weights.extend(others);
+        distribution
+    }
+
+    /// Returns the `(receipt_index, action_index, gas)` of every function call action which
+    /// would receive a share of `unused_gas` from [`ReceiptManager::distribute_unused_gas`],
+    /// without distributing it.
+    pub fn preview_gas_distribution(&self, unused_gas: Gas) -> Vec<(u64, u64, Gas)> {
+        self.gas_weights
+            .iter()
+            .zip(self.gas_to_add(unused_gas))
+            .map(|(entry, gas)| {
+                (entry.index.receipt_index as u64, entry.index.action_index as u64, gas)
+            })
+            .collect()
+    }
+
+    /// Returns the gas added to each weighted action when `unused_gas` is distributed.
+    ///
+    /// Actions whose prepaid gas is a floor only receive the part of their share above it.
+    fn gas_to_add(&self, unused_gas: Gas) -> Vec<Gas> {
+        self.gas_weights
+            .iter()
+            .zip(split_unused_gas(&self.gas_weights, unused_gas))
+            .map(|(entry, share)| {
+                if entry.prepaid_gas_is_floor {
+                    let prepaid_gas =
+                        get_function_call_action(&self.action_receipts, entry.index).gas;
+                    share.saturating_sub(prepaid_gas)
+                } else {
+                    share
+                }
+            })
+            .collect()
+    }
+
+    /// Sets the gas weight of the already appended function call action `action_index` of the
+    /// receipt `receipt_index`, which then receives a share of the unused gas like actions
+    /// appended with a weight. A zero weight makes the action stop participating.
+    ///
+    /// Fails with [`HostError::InvalidReceiptIndex`] if the receipt doesn't exist and with
+    /// [`HostError::InvalidActionIndex`] if the action doesn't exist or isn't a function call.
+    pub fn set_gas_weight(
+        &mut self,
+        receipt_index: ReceiptIndex,
+        action_index: usize,
+        weight: GasWeight,
+    ) -> logic::Result<()> {
+        let (_, receipt) = self
+            .action_receipts
+            .get(receipt_index as usize)
+            .ok_or(HostError::InvalidReceiptIndex { receipt_index })?;
+        if !matches!(receipt.actions.get(action_index), Some(Action::FunctionCall(_))) {
+            return Err(HostError::InvalidActionIndex { action_index: action_index as u64 }.into());
+        }
+
+        let index = FunctionCallActionIndex { receipt_index: receipt_index as usize, action_index };
+        match self.gas_weights.iter().position(|entry| entry.index == index) {
+            Some(position) if weight.0 == 0 => {
+                self.gas_weights.remove(position);
+            }
+            Some(position) => self.gas_weights[position].weight = weight,
+            None if weight.0 == 0 => {}
+            None => self.gas_weights.push(GasWeightEntry {
+                index,
+                weight,
+                max_gas_per_action: None,
+                min_gas_per_action: None,
+                prepaid_gas_is_floor: false,
+            }),
+        }
+        Ok(())
+    }
+
+    /// Removes the gas weight of the function call action `action_index` of the receipt
+    /// `receipt_index`, which then keeps only its prepaid gas when the unused gas is
+    /// distributed.
+    ///
+    /// Returns false if the action doesn't have a gas weight.
+    pub fn clear_gas_weight(&mut self, receipt_index: ReceiptIndex, action_index: usize) -> bool {
+        let index = FunctionCallActionIndex { receipt_index: receipt_index as usize, action_index };
+        match self.gas_weights.iter().position(|entry| entry.index == index) {
+            Some(position) => {
+                self.gas_weights.remove(position);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Caps the gas that the function call action `action_index` of the receipt
+    /// `receipt_index` receives from [`ReceiptManager::distribute_unused_gas`] at `max_gas`.
+    /// The excess goes to the other weighted actions.
+    ///
+    /// Returns false if the action doesn't have a gas weight.
+    pub fn set_max_gas_per_action(
+        &mut self,
+        receipt_index: ReceiptIndex,
+        action_index: usize,
+        max_gas: Gas,
+    ) -> bool {
+        let index = FunctionCallActionIndex { receipt_index: receipt_index as usize, action_index };
+        match self.gas_weights.iter_mut().find(|entry| entry.index == index) {
+            Some(entry) => {
+                entry.max_gas_per_action = Some(max_gas);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Makes [`ReceiptManager::distribute_unused_gas`] give the function call action
+    /// `action_index` of the receipt `receipt_index` at least `min_gas` before the rest of the
+    /// unused gas is split by weight, as long as the unused gas covers the minimums of all
+    /// actions.
+    ///
+    /// Returns false if the action doesn't have a gas weight.
+    pub fn set_min_gas_per_action(
+        &mut self,
+        receipt_index: ReceiptIndex,
+        action_index: usize,
+        min_gas: Gas,
+    ) -> bool {
+        let index = FunctionCallActionIndex { receipt_index: receipt_index as usize, action_index };
+        match self.gas_weights.iter_mut().find(|entry| entry.index == index) {
+            Some(entry) => {
+                entry.min_gas_per_action = Some(min_gas);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Divides all stored gas weights by their greatest common divisor.
+    ///
+    /// Proportions between the weights are preserved, so the result of
+    /// [`ReceiptManager::distribute_unused_gas`] is not affected, but the weights and their sum
+    /// are kept as small as possible.
+    pub fn normalize_gas_weights(&mut self) {
+        let divisor = self.gas_weights.iter().fold(0, |acc, entry| gcd(acc, entry.weight.0));
+        if divisor > 1 {
+            for entry in &mut self.gas_weights {
+                entry.weight.0 /= divisor;
+            }
+        }
+    }
+
+    /// Whether [`ReceiptManager::distribute_unused_gas`] would distribute any gas, i.e. it
+    /// wouldn't return [`GasDistribution::NoRatios`]. Allows skipping the distribution cheaply.
+    pub fn has_distributable_weights(&self) -> bool {
+        self.gas_weights.iter().any(|entry| entry.weight.0 > 0)
+    }
+
+    /// Returns the `(receipt_index, action_index, weight)` of every function call action which
+    /// will receive a share of the unused gas, in the order the actions were appended.
+    pub fn pending_gas_weights(&self) -> Vec<(u64, u64, u64)> {
+        self.gas_weights
+            .iter()
+            .map(|entry| {
+                (entry.index.receipt_index as u64, entry.index.action_index as u64, entry.weight.0)
+            })
+            .collect()
+    }
+
+    /// Returns the indices of the receipts which have no actions, in increasing order.
+    ///
+    /// Such receipts are created by [`ReceiptManager::create_receipt`] but never populated, see
+    /// also [`ReceiptManager::compact`].
+    pub fn empty_receipt_indices(&self) -> Vec<ReceiptIndex> {
+        self.action_receipts
+            .iter()
+            .enumerate()
+            .filter(|(_, (_, receipt))| receipt.actions.is_empty())
+            .map(|(index, _)| index as ReceiptIndex)
+            .collect()
+    }
+
+    /// Removes all receipts which have no actions and returns how many receipts were removed.
+    ///
+    /// Gas weights are remapped to the new positions of their receipts. Weights attached to
+    /// removed receipts are dropped.
+    ///
+    /// Note that receipt indices previously handed out for receipts after the first removed
+    /// one are invalidated. Data dependencies on a removed receipt are not cleaned up: the
+    /// corresponding `input_data_ids` of its dependents become dangling.
+    pub fn compact(&mut self) -> usize {
+        self.retain_receipts(|_, receipt| !receipt.actions.is_empty()).len()
+    }
+
+    /// Removes the receipt `receipt_index` and returns its metadata, e.g. to roll back a
+    /// receipt which turned out to be unnecessary.
+    ///
+    /// Indices of the later receipts shift down by one and their gas weights and action slots
+    /// follow them, while those of the removed receipt are dropped. Data dependencies on the
+    /// removed receipt are detached on both sides: the receipts it depends on no longer send it
+    /// their output and the receipts depending on it no longer wait for its output. Deposits
+    /// attached by the removed actions no longer count towards the deposit limit.
+    pub fn remove_receipt(
+        &mut self,
+        receipt_index: ReceiptIndex,
+    ) -> logic::Result<ReceiptMetadata> {
+        if receipt_index as usize >= self.action_receipts.len() {
+            return Err(HostError::InvalidReceiptIndex { receipt_index }.into());
+        }
+        let (_, removed) = self.action_receipts.remove(receipt_index as usize);
+        for action in &removed.actions {
+            if let Action::Transfer(TransferAction { deposit })
+            | Action::FunctionCall(FunctionCallAction { deposit, .. }) = action
+            {
+                // The deposit was added to the total when the action was appended.
+                self.attached_deposit = self.attached_deposit.saturating_sub(*deposit);
+            }
+        }
+        let removed_index = receipt_index as usize;
+        self.remap_action_indices(|mut index| {
+            if index.receipt_index == removed_index {
+                return None;
+            }
+            if index.receipt_index > removed_index {
+                index.receipt_index -= 1;
+            }
+            Some(index)
+        });
+
+        let output_data_ids: Vec<CryptoHash> =
+            removed.output_data_receivers.iter().map(|receiver| receiver.data_id).collect();
+        for (_, receipt) in &mut self.action_receipts {
+            receipt
+                .output_data_receivers
+                .retain(|receiver| !removed.input_data_ids.contains(&receiver.data_id));
+            receipt.input_data_ids.retain(|data_id| !output_data_ids.contains(data_id));
+        }
+        Ok(removed)
+    }
+
+    /// Reserves capacity for at least `additional` more receipts, avoiding reallocations when
+    /// many receipts are about to be created.
+    pub fn reserve_receipts(&mut self, additional: usize) {
+        self.action_receipts.reserve(additional);
+    }
+
+    /// Returns the number of receipts created so far.
+    pub fn receipt_count(&self) -> usize {
+        self.action_receipts.len()
+    }
 
-        let mut distributed = 0;
-        for (action_index, GasWeight(weight)) in &self.gas_weights {
-            // Multiplication is done in u128 with max values of u64::MAX so this cannot overflow.
-            // Division result can be truncated to 64 bits because gas_weight_sum >= weight.
-            let assigned_gas = (unused_gas as u128 * *weight as u128 / gas_weight_sum) as u64;
+    /// Returns the number of actions across all receipts.
+    pub fn total_action_count(&self) -> usize {
+        self.action_receipts.iter().map(|(_, receipt)| receipt.actions.len()).sum()
+    }
 
-            distribute_gas(action_index, assigned_gas as u64);
+    /// Returns the number of data receivers across all receipts, i.e. the number of data
+    /// receipts the scheduled receipts will send to each other.
+    pub fn total_data_receivers(&self) -> usize {
+        self.action_receipts.iter().map(|(_, receipt)| receipt.output_data_receivers.len()).sum()
+    }
 
-            distributed += assigned_gas
+    /// Returns the receivers of the receipts without duplicates, in the order they are first
+    /// seen. Useful to estimate the cross-shard fan-out of a contract call.
+    pub fn distinct_receivers(&self) -> Vec<AccountId> {
+        let mut receivers: Vec<AccountId> = Vec::new();
+        for (receiver_id, _) in &self.action_receipts {
+            if !receivers.contains(receiver_id) {
+                receivers.push(receiver_id.clone());
+            }
         }
+        receivers
+    }
 
-        // Distribute remaining gas to final action.
-        if let Some((last_idx, _)) = self.gas_weights.last() {
-            distribute_gas(last_idx, unused_gas - distributed);
+    /// Removes and returns all receipts. Gas weights are dropped along with the receipts and
+    /// unfilled action slots are left out of them.
+    ///
+    /// Actions are put in canonical order if enabled with
+    /// [`ReceiptManager::set_sort_actions_on_take`].
+    pub fn take_receipts(&mut self) -> ActionReceipts {
+        let mut receipts = self.retain_receipts(|_, _| false);
+        if self.sort_actions_on_take {
+            for (_, receipt) in &mut receipts {
+                // The sort is stable, so the other actions keep their order.
+                receipt.actions.sort_by_key(|action| match action {
+                    Action::CreateAccount(_) => 0,
+                    Action::DeleteAccount(_) => 2,
+                    _ => 1,
+                });
+            }
         }
+        receipts
+    }
+
+    /// Removes all receipts and turns them into [`Receipt`]s sent by `predecessor_id` on
+    /// behalf of the given signer, paired with their index in this manager.
+    ///
+    /// Receipt ids are left as default, the runtime generates them once the receipts are
+    /// collected. Gas weights are dropped along with the receipts. Receipts get `gas_price`
+    /// unless it is overridden with [`ReceiptManager::set_receipt_gas_price`].
+    pub fn take_receipts_indexed(
+        &mut self,
+        predecessor_id: &AccountId,
+        signer_id: &AccountId,
+        signer_public_key: &PublicKey,
+        gas_price: Balance,
+    ) -> Vec<(ReceiptIndex, Receipt)> {
+        self.take_receipts()
+            .into_iter()
+            .enumerate()
+            .map(|(index, (receiver_id, receipt))| {
+                let receipt = Receipt {
+                    predecessor_id: predecessor_id.clone(),
+                    receiver_id,
+                    receipt_id: CryptoHash::default(),
+                    receipt: ReceiptEnum::Action(ActionReceipt {
+                        signer_id: signer_id.clone(),
+                        signer_public_key: signer_public_key.clone(),
+                        gas_price: receipt.gas_price.unwrap_or(gas_price),
+                        output_data_receivers: receipt.output_data_receivers,
+                        input_data_ids: receipt.input_data_ids,
+                        actions: receipt.actions,
+                    }),
+                };
+                (index as ReceiptIndex, receipt)
+            })
+            .collect()
+    }
+
+    /// Same as [`ReceiptManager::take_receipts_indexed`], additionally returning
+    /// [`TakeReceiptsDiagnostics`] about the taken receipts.
+    pub fn take_receipts_with_diagnostics(
+        &mut self,
+        predecessor_id: &AccountId,
+        signer_id: &AccountId,
+        signer_public_key: &PublicKey,
+        gas_price: Balance,
+    ) -> (Vec<(ReceiptIndex, Receipt)>, TakeReceiptsDiagnostics) {
+        let receipts =
+            self.take_receipts_indexed(predecessor_id, signer_id, signer_public_key, gas_price);
+        let reflexive_receipts = receipts
+            .iter()
+            .filter(|(_, receipt)| &receipt.receiver_id == predecessor_id)
+            .map(|(index, _)| *index)
+            .collect();
+        (receipts, TakeReceiptsDiagnostics { reflexive_receipts })
+    }
+
+    /// Same as [`ReceiptManager::take_receipts_indexed`] with the gas price given to
+    /// [`ReceiptManager::with_gas_price`], for managers serving a single transaction.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the manager wasn't created with [`ReceiptManager::with_gas_price`].
+    pub fn take_receipts_simple(
+        &mut self,
+        predecessor_id: &AccountId,
+        signer_id: &AccountId,
+        signer_public_key: &PublicKey,
+    ) -> Vec<Receipt> {
+        let gas_price = self.gas_price.expect("manager should be created with a gas price");
+        self.take_receipts_indexed(predecessor_id, signer_id, signer_public_key, gas_price)
+            .into_iter()
+            .map(|(_, receipt)| receipt)
+            .collect()
+    }
+
+    /// Drops all receipts, gas weights and action slots and resets the attached deposit,
+    /// keeping the configured limits and gas price. Previously handed out receipt indices
+    /// become invalid.
+    pub fn clear(&mut self) {
+        self.action_receipts.clear();
         self.gas_weights.clear();
-        GasDistribution::All
+        self.action_slots.clear();
+        self.attached_deposit = 0;
+    }
+
+    /// Removes and returns the receipts destined for `receiver_id`, keeping the order of both
+    /// the taken and the remaining receipts.
+    ///
+    /// The same caveats as for [`ReceiptManager::compact`] apply: indices of the remaining
+    /// receipts may shift and gas weights attached to the taken receipts are dropped, so
+    /// unused gas should be distributed before taking receipts.
+    pub fn take_receipts_for(&mut self, receiver_id: &AccountId) -> ActionReceipts {
+        self.retain_receipts(|receiver, _| receiver != receiver_id)
+    }
+
+    /// Keeps only the receipts for which `keep` returns true and returns the removed ones.
+    /// Gas weights and action slots of the kept receipts are remapped to their new positions.
+    /// Unfilled action slots of the removed receipts are stripped.
+    fn retain_receipts(
+        &mut self,
+        mut keep: impl FnMut(&AccountId, &ReceiptMetadata) -> bool,
+    ) -> ActionReceipts {
+        let mut new_indices = Vec::with_capacity(self.action_receipts.len());
+        let mut kept = Vec::with_capacity(self.action_receipts.len());
+        let mut removed = Vec::new();
+        for (index, (receiver, mut receipt)) in
+            std::mem::take(&mut self.action_receipts).into_iter().enumerate()
+        {
+            if keep(&receiver, &receipt) {
+                new_indices.push(Some(kept.len()));
+                kept.push((receiver, receipt));
+            } else {
+                new_indices.push(None);
+                self.strip_action_slots(index, &mut receipt);
+                removed.push((receiver, receipt));
+            }
+        }
+
+        self.action_receipts = kept;
+        self.remap_action_indices(|index| {
+            Some(FunctionCallActionIndex {
+                receipt_index: new_indices[index.receipt_index]?,
+                action_index: index.action_index,
+            })
+        });
+        removed
+    }
+
+    /// Returns a human-readable multi-line summary of the receipts for debugging.
+    ///
+    /// Each receipt is described by its receiver and the number of its input data dependencies
+    /// and output data receivers, followed by one line per action with its key parameters.
+    /// Unfilled action slots are left out.
+    pub fn describe(&self) -> String {
+        let mut out = String::new();
+        for (index, (receiver_id, receipt)) in self.action_receipts.iter().enumerate() {
+            writeln!(
+                out,
+                "receipt {} to {}: {} input data dependencies, {} output data receivers",
+                index,
+                receiver_id,
+                receipt.input_data_ids.len(),
+                receipt.output_data_receivers.len(),
+            )
+            .unwrap();
+            for (_, action) in self.filled_actions(index, receipt) {
+                out.push_str("  ");
+                describe_action(&mut out, action).unwrap();
+                out.push('\n');
+            }
+        }
+        out
+    }
+
+    /// Returns the receipts as a JSON array, e.g. to compare them against an expected snapshot
+    /// in tests. Every receipt is an object with its receiver, data dependencies and actions,
+    /// which are serialized the same way as in RPC responses. Unfilled action slots are left
+    /// out.
+    #[cfg(feature = "test_features")]
+    pub fn to_json(&self) -> serde_json::Value {
+        self.action_receipts
+            .iter()
+            .enumerate()
+            .map(|(index, (receiver_id, receipt))| {
+                let actions: Vec<&Action> =
+                    self.filled_actions(index, receipt).map(|(_, action)| action).collect();
+                serde_json::json!({
+                    "receiver_id": receiver_id,
+                    "input_data_ids": receipt.input_data_ids,
+                    "output_data_receivers": receipt.output_data_receivers,
+                    "actions": actions,
+                })
+            })
+            .collect()
+    }
+
+    /// Returns the size in bytes of the borsh-serialized actions queued for the receipt, or
+    /// `None` if there is no receipt with the given index.
+    ///
+    /// This is meant as an estimate of the storage footprint of the receipt before it is sent,
+    /// so unfilled action slots, which are never sent, are left out.
+    pub fn estimated_storage_bytes(&self, receipt_index: ReceiptIndex) -> Option<u64> {
+        let (_, receipt) = self.action_receipts.get(receipt_index as usize)?;
+        let actions: Vec<&Action> = self
+            .filled_actions(receipt_index as usize, receipt)
+            .map(|(_, action)| action)
+            .collect();
+        let bytes = actions.try_to_vec().expect("borsh serialization into a vec cannot fail");
+        Some(bytes.len() as u64)
+    }
+
+    /// Returns the hash of the borsh-serialized actions of the receipt `receipt_index`, or
+    /// `None` if the receipt doesn't exist. Receipts with equal actions have equal hashes
+    /// regardless of their receivers and data dependencies. Unfilled action slots are left out.
+    pub fn action_list_hash(&self, receipt_index: ReceiptIndex) -> Option<CryptoHash> {
+        let (_, receipt) = self.action_receipts.get(receipt_index as usize)?;
+        let actions: Vec<&Action> = self
+            .filled_actions(receipt_index as usize, receipt)
+            .map(|(_, action)| action)
+            .collect();
+        Some(CryptoHash::hash_borsh(&actions))
+    }
+
+    /// Returns the sum of [`ReceiptManager::estimated_storage_bytes`] over all receipts, i.e.
+    /// the borsh-serialized size of all pending actions.
+    pub fn total_serialized_size(&self) -> u64 {
+        (0..self.action_receipts.len() as ReceiptIndex)
+            .filter_map(|receipt_index| self.estimated_storage_bytes(receipt_index))
+            .sum()
+    }
+}
+
+fn describe_action(out: &mut String, action: &Action) -> std::fmt::Result {
+    match action {
+        Action::CreateAccount(_) => write!(out, "CreateAccount"),
+        Action::DeployContract(DeployContractAction { code }) => {
+            write!(out, "DeployContract code_len={}", code.len())
+        }
+        Action::FunctionCall(FunctionCallAction { method_name, args, gas, deposit }) => write!(
+            out,
+            "FunctionCall method={} args_len={} gas={} deposit={}",
+            method_name,
+            args.len(),
+            gas,
+            deposit
+        ),
+        Action::Transfer(TransferAction { deposit }) => write!(out, "Transfer deposit={}", deposit),
+        Action::Stake(StakeAction { stake, public_key }) => {
+            write!(out, "Stake stake={} public_key={}", stake, public_key)
+        }
+        Action::AddKey(AddKeyAction { public_key, access_key }) => match &access_key.permission {
+            AccessKeyPermission::FullAccess => {
+                write!(out, "AddKey public_key={} full_access", public_key)
+            }
+            AccessKeyPermission::FunctionCall(FunctionCallPermission {
+                allowance,
+                receiver_id,
+                method_names,
+            }) => write!(
+                out,
+                "AddKey public_key={} function_call receiver={} methods={:?} allowance={:?}",
+                public_key, receiver_id, method_names, allowance
+            ),
+        },
+        Action::DeleteKey(DeleteKeyAction { public_key }) => {
+            write!(out, "DeleteKey public_key={}", public_key)
+        }
+        Action::DeleteAccount(DeleteAccountAction { beneficiary_id }) => {
+            write!(out, "DeleteAccount beneficiary={}", beneficiary_id)
+        }
+        // Feature-gated actions which are never created by the receipt manager.
+        #[allow(unreachable_patterns)]
+        action => write!(out, "{:?}", action),
+    }
+}
+
+/// Appends actions to a receipt of a [`ReceiptManager`] through chained calls.
+///
+/// Each method forwards to the corresponding `append_action_*` method of the manager. The
+/// first error is kept and returned by [`ReceiptBuilder::build`]; actions chained after it are
+/// not appended.
+pub struct ReceiptBuilder<'a> {
+    manager: &'a mut ReceiptManager,
+    receipt_index: ReceiptIndex,
+    result: logic::Result<()>,
+}
+
+impl<'a> ReceiptBuilder<'a> {
+    /// Starts appending actions to the existing receipt `receipt_index` of `manager`.
+    pub fn new(manager: &'a mut ReceiptManager, receipt_index: ReceiptIndex) -> Self {
+        Self { manager, receipt_index, result: Ok(()) }
+    }
+
+    fn append(
+        mut self,
+        append: impl FnOnce(&mut ReceiptManager, ReceiptIndex) -> logic::Result<()>,
+    ) -> Self {
+        if self.result.is_ok() {
+            self.result = append(self.manager, self.receipt_index);
+        }
+        self
+    }
+
+    pub fn create_account(self) -> Self {
+        self.append(|manager, index| manager.append_action_create_account(index))
+    }
+
+    pub fn deploy_contract(self, code: Vec<u8>) -> Self {
+        self.append(|manager, index| manager.append_action_deploy_contract(index, code))
+    }
+
+    pub fn function_call(
+        self,
+        method_name: Vec<u8>,
+        args: Vec<u8>,
+        attached_deposit: Balance,
+        prepaid_gas: Gas,
+        gas_weight: GasWeight,
+    ) -> Self {
+        self.append(|manager, index| {
+            manager.append_action_function_call_weight(
+                index,
+                method_name,
+                args,
+                attached_deposit,
+                prepaid_gas,
+                gas_weight,
+            )
+        })
+    }
+
+    pub fn transfer(self, deposit: Balance) -> Self {
+        self.append(|manager, index| manager.append_action_transfer(index, deposit))
+    }
+
+    pub fn stake(self, stake: Balance, public_key: Vec<u8>) -> Self {
+        self.append(|manager, index| manager.append_action_stake(index, stake, public_key))
+    }
+
+    pub fn unstake(self, public_key: Vec<u8>) -> Self {
+        self.append(|manager, index| manager.append_action_unstake(index, public_key))
+    }
+
+    pub fn add_key_with_full_access(self, public_key: Vec<u8>, nonce: Nonce) -> Self {
+        self.append(|manager, index| {
+            manager.append_action_add_key_with_full_access(index, public_key, nonce)
+        })
+    }
+
+    pub fn add_key_with_function_call(
+        self,
+        public_key: Vec<u8>,
+        nonce: Nonce,
+        allowance: Option<Balance>,
+        receiver_id: AccountId,
+        method_names: Vec<Vec<u8>>,
+    ) -> Self {
+        self.append(|manager, index| {
+            manager.append_action_add_key_with_function_call(
+                index,
+                public_key,
+                nonce,
+                allowance,
+                receiver_id,
+                method_names,
+            )
+        })
+    }
+
+    pub fn delete_key(self, public_key: Vec<u8>) -> Self {
+        self.append(|manager, index| manager.append_action_delete_key(index, public_key))
+    }
+
+    pub fn delete_account(self, beneficiary_id: AccountId) -> Self {
+        self.append(|manager, index| manager.append_action_delete_account(index, beneficiary_id))
+    }
+
+    /// Returns the index of the receipt, or the first error hit while appending actions.
+    pub fn build(self) -> logic::Result<ReceiptIndex> {
+        self.result.map(|()| self.receipt_index)
     }
 }
@@ -1,12 +1,14 @@
 use crate::tests::fixtures::get_context;
 use crate::tests::helpers::*;
-use crate::tests::vm_logic_builder::VMLogicBuilder;
+use crate::tests::vm_logic_builder::{VMLogicBuilder, LATEST_PROTOCOL_VERSION};
 use crate::types::PromiseResult;
 use crate::VMLogic;
 use borsh::BorshSerialize;
 use near_account_id::AccountId;
 use near_crypto::PublicKey;
 use near_primitives::transaction::Action;
+use near_primitives::version::ProtocolFeature;
+use near_vm_errors::{HostError, VMLogicError};
 use serde::Serialize;
 use serde_json;
 
@@ -421,3 +423,88 @@ fn test_promise_batch_then() {
     ]);
     assert_eq!(&serde_json::to_string(&vm_receipts(&logic)).unwrap(), &expected.to_string());
 }
+
+#[test]
+fn test_promise_batch_action_invalid_public_key() {
+    // The key type is followed by no key data.
+    let key = [0u8];
+    for (protocol_version, with_reason) in [
+        (LATEST_PROTOCOL_VERSION, cfg!(feature = "protocol_feature_public_key_error_reason")),
+        (ProtocolFeature::AltBn128.protocol_version(), false),
+    ] {
+        let mut logic_builder = VMLogicBuilder::default();
+        logic_builder.current_protocol_version = protocol_version;
+        let mut logic = logic_builder.build(get_context(vec![], false));
+        let index =
+            promise_create(&mut logic, b"rick.test", 0, 0).expect("should create a promise");
+
+        let result =
+            logic.promise_batch_action_delete_key(index, key.len() as u64, key.as_ptr() as _);
+        if with_reason {
+            assert!(
+                matches!(
+                    result,
+                    Err(VMLogicError::HostError(HostError::InvalidPublicKeyWithReason { .. }))
+                ),
+                "{:?}",
+                result
+            );
+        } else {
+            assert_eq!(result, Err(HostError::InvalidPublicKey.into()));
+        }
+    }
+}
+
+#[test]
+fn test_promise_batch_action_function_call_invalid_method_name() {
+    let method_name = b"meth\xffod";
+    for (protocol_version, with_offset) in [
+        (LATEST_PROTOCOL_VERSION, cfg!(feature = "protocol_feature_method_name_error_offset")),
+        (ProtocolFeature::AltBn128.protocol_version(), false),
+    ] {
+        let mut logic_builder = VMLogicBuilder::default();
+        logic_builder.current_protocol_version = protocol_version;
+        let mut logic = logic_builder.build(get_context(vec![], false));
+        let index =
+            promise_create(&mut logic, b"rick.test", 0, 0).expect("should create a promise");
+
+        let result = logic.promise_batch_action_function_call(
+            index,
+            method_name.len() as u64,
+            method_name.as_ptr() as _,
+            0,
+            method_name.as_ptr() as _,
+            0u128.to_le_bytes().as_ptr() as _,
+            0,
+        );
+        let expected = if with_offset {
+            HostError::InvalidMethodNameUtf8 { valid_up_to: 4 }
+        } else {
+            HostError::InvalidMethodName
+        };
+        assert_eq!(result, Err(expected.into()));
+    }
+}
+
+#[test]
+fn test_promise_batch_action_delete_account_system_beneficiary() {
+    let beneficiary_id = b"system";
+    for (protocol_version, rejected) in [
+        (LATEST_PROTOCOL_VERSION, cfg!(feature = "protocol_feature_reject_system_beneficiary")),
+        (ProtocolFeature::AltBn128.protocol_version(), false),
+    ] {
+        let mut logic_builder = VMLogicBuilder::default();
+        logic_builder.current_protocol_version = protocol_version;
+        let mut logic = logic_builder.build(get_context(vec![], false));
+        let index =
+            promise_create(&mut logic, b"rick.test", 0, 0).expect("should create a promise");
+
+        let result = logic.promise_batch_action_delete_account(
+            index,
+            beneficiary_id.len() as u64,
+            beneficiary_id.as_ptr() as _,
+        );
+        let expected = if rejected { Err(HostError::InvalidAccountId.into()) } else { Ok(()) };
+        assert_eq!(result, expected);
+    }
+}
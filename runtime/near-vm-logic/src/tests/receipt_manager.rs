@@ -0,0 +1,1976 @@
+use crate::mocks::mock_external::MockedExternal;
+use crate::receipt_manager::{
+    distribute, ActionSpec, ReceiptBuilder, ReceiptManager, ReceiptManagerMetrics,
+    TakeReceiptsDiagnostics,
+};
+use crate::types::ReceiptIndex;
+use crate::{dependencies, External, ValuePtr};
+use borsh::{BorshDeserialize, BorshSerialize};
+use near_crypto::{KeyType, PublicKey};
+use near_primitives::receipt::{DataReceiver, ReceiptEnum};
+use near_primitives::transaction::{
+    Action, AddKeyAction, CreateAccountAction, DeleteAccountAction, DeleteKeyAction,
+    DeployContractAction, FunctionCallAction, StakeAction, TransferAction,
+};
+use near_primitives::types::TrieNodesCount;
+use near_primitives_core::account::{AccessKey, AccessKeyPermission, FunctionCallPermission};
+use near_primitives_core::hash::CryptoHash;
+use near_primitives_core::runtime::fees::RuntimeFeesConfig;
+use near_primitives_core::types::{AccountId, Balance, Gas, GasDistribution, GasWeight};
+use near_vm_errors::{HostError, VMLogicError};
+use std::sync::{Arc, Mutex};
+
+fn create_receipt(
+    manager: &mut ReceiptManager,
+    ext: &mut MockedExternal,
+    receiver_id: &str,
+) -> ReceiptIndex {
+    manager.create_receipt(ext, vec![], receiver_id.parse::<AccountId>().unwrap()).unwrap()
+}
+
+fn append_weighted_call(manager: &mut ReceiptManager, receipt_index: ReceiptIndex, weight: u64) {
+    manager
+        .append_action_function_call_weight(
+            receipt_index,
+            b"method".to_vec(),
+            vec![],
+            0,
+            0,
+            GasWeight(weight),
+        )
+        .unwrap();
+}
+
+fn function_call_gas(manager: &ReceiptManager, receipt_index: usize) -> Vec<Gas> {
+    manager.action_receipts[receipt_index]
+        .1
+        .actions
+        .iter()
+        .filter_map(|action| match action {
+            Action::FunctionCall(FunctionCallAction { gas, .. }) => Some(*gas),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn test_compact_remaps_gas_weights() {
+    let mut ext = MockedExternal::new();
+    let mut manager = ReceiptManager::default();
+    let first = create_receipt(&mut manager, &mut ext, "alice.near");
+    create_receipt(&mut manager, &mut ext, "bob.near");
+    let third = create_receipt(&mut manager, &mut ext, "carol.near");
+    append_weighted_call(&mut manager, first, 1);
+    append_weighted_call(&mut manager, third, 3);
+
+    assert_eq!(manager.compact(), 1);
+    assert_eq!(manager.action_receipts.len(), 2);
+    assert_eq!(manager.action_receipts[1].0.as_ref(), "carol.near");
+
+    // Distribution would panic if the weight of the third receipt still pointed at index 2.
+    assert_eq!(manager.distribute_unused_gas(100), GasDistribution::All);
+    assert_eq!(function_call_gas(&manager, 0), vec![25]);
+    assert_eq!(function_call_gas(&manager, 1), vec![75]);
+}
+
+#[test]
+fn test_duplicate_actions() {
+    let mut ext = MockedExternal::new();
+    let mut manager = ReceiptManager::default();
+    let receipt_index = create_receipt(&mut manager, &mut ext, "alice.near");
+    assert_eq!(manager.duplicate_actions(receipt_index), Some(vec![]));
+
+    manager.append_action_transfer(receipt_index, 10).unwrap();
+    manager.append_action_transfer(receipt_index, 20).unwrap();
+    manager.append_action_transfer(receipt_index, 10).unwrap();
+    assert_eq!(manager.duplicate_actions(receipt_index), Some(vec![(0, 2)]));
+    assert_eq!(manager.duplicate_actions(receipt_index + 1), None);
+}
+
+#[test]
+fn test_produces_output() {
+    let mut ext = MockedExternal::new();
+    let mut manager = ReceiptManager::default();
+    let first = create_receipt(&mut manager, &mut ext, "alice.near");
+    let second =
+        manager.create_receipt(&mut ext, vec![first], "bob.near".parse().unwrap()).unwrap();
+
+    assert_eq!(manager.produces_output(first), Some(true));
+    assert_eq!(manager.produces_output(second), Some(false));
+    assert_eq!(manager.produces_output(second + 1), None);
+}
+
+#[test]
+fn test_soft_max_total_actions() {
+    let mut ext = MockedExternal::new();
+    let mut manager = ReceiptManager::default();
+    let receipt_index = create_receipt(&mut manager, &mut ext, "alice.near");
+    let transfer = |deposit| Action::Transfer(TransferAction { deposit });
+    assert_eq!(manager.append_action_with_backpressure(receipt_index, transfer(1)), Ok(false));
+
+    manager.set_soft_max_total_actions(2);
+    assert!(!manager.is_queue_full());
+    assert_eq!(manager.append_action_with_backpressure(receipt_index, transfer(2)), Ok(true));
+    assert_eq!(manager.append_action_with_backpressure(receipt_index, transfer(3)), Ok(true));
+    assert!(manager.is_queue_full());
+    assert_eq!(manager.action_receipts[0].1.actions, vec![transfer(1), transfer(2), transfer(3)]);
+}
+
+#[test]
+fn test_total_data_receivers() {
+    let mut ext = MockedExternal::new();
+    let mut manager = ReceiptManager::default();
+    let first = create_receipt(&mut manager, &mut ext, "alice.near");
+    let second = create_receipt(&mut manager, &mut ext, "bob.near");
+    assert_eq!(manager.total_data_receivers(), 0);
+
+    manager.create_receipt(&mut ext, vec![first, second], "carol.near".parse().unwrap()).unwrap();
+    manager.create_receipt(&mut ext, vec![first], "dave.near".parse().unwrap()).unwrap();
+    assert_eq!(manager.total_data_receivers(), 3);
+}
+
+#[test]
+fn test_remove_receipt_remaps_gas_weights() {
+    let mut ext = MockedExternal::new();
+    let mut manager = ReceiptManager::default();
+    let first = create_receipt(&mut manager, &mut ext, "alice.near");
+    let second = create_receipt(&mut manager, &mut ext, "bob.near");
+    let third = create_receipt(&mut manager, &mut ext, "carol.near");
+    append_weighted_call(&mut manager, first, 1);
+    append_weighted_call(&mut manager, second, 2);
+    append_weighted_call(&mut manager, third, 3);
+
+    let removed = manager.remove_receipt(second).unwrap();
+    assert_eq!(removed.actions.len(), 1);
+    assert_eq!(manager.action_receipts.len(), 2);
+    assert_eq!(manager.action_receipts[1].0.as_ref(), "carol.near");
+
+    assert_eq!(manager.distribute_unused_gas(100), GasDistribution::All);
+    assert_eq!(function_call_gas(&manager, 0), vec![25]);
+    assert_eq!(function_call_gas(&manager, 1), vec![75]);
+
+    assert_eq!(
+        manager.remove_receipt(2).unwrap_err(),
+        VMLogicError::HostError(HostError::InvalidReceiptIndex { receipt_index: 2 })
+    );
+}
+
+#[test]
+fn test_remove_receipt_releases_deposit() {
+    let mut ext = MockedExternal::new();
+    let mut manager = ReceiptManager::default();
+    manager.set_deposit_limit(10);
+    let first = create_receipt(&mut manager, &mut ext, "alice.near");
+    let second = create_receipt(&mut manager, &mut ext, "bob.near");
+    manager.append_action_transfer(second, 4).unwrap();
+    manager
+        .append_action_function_call_weight(second, b"method".to_vec(), vec![], 6, 0, GasWeight(0))
+        .unwrap();
+    assert_eq!(
+        manager.append_action_transfer(first, 1),
+        Err(VMLogicError::HostError(HostError::BalanceExceeded))
+    );
+
+    manager.remove_receipt(second).unwrap();
+    manager.append_action_transfer(first, 10).unwrap();
+}
+
+#[test]
+fn test_remove_receipt_detaches_data_receivers() {
+    let mut ext = MockedExternal::new();
+    let mut manager = ReceiptManager::default();
+    let first = create_receipt(&mut manager, &mut ext, "alice.near");
+    let second =
+        manager.create_receipt(&mut ext, vec![first], "bob.near".parse().unwrap()).unwrap();
+    let third =
+        manager.create_receipt(&mut ext, vec![second], "carol.near".parse().unwrap()).unwrap();
+    let unrelated =
+        manager.create_receipt(&mut ext, vec![first], "dave.near".parse().unwrap()).unwrap();
+
+    manager.remove_receipt(second).unwrap();
+    let (_, first) = &manager.action_receipts[first as usize];
+    assert_eq!(first.output_data_receivers.len(), 1);
+    assert_eq!(first.output_data_receivers[0].receiver_id.as_ref(), "dave.near");
+    let (_, third) = &manager.action_receipts[third as usize - 1];
+    assert!(third.input_data_ids.is_empty());
+    let (_, unrelated) = &manager.action_receipts[unrelated as usize - 1];
+    assert_eq!(unrelated.input_data_ids, vec![first.output_data_receivers[0].data_id]);
+}
+
+#[test]
+fn test_estimated_storage_bytes() {
+    let mut ext = MockedExternal::new();
+    let mut manager = ReceiptManager::default();
+    let receipt_index = create_receipt(&mut manager, &mut ext, "alice.near");
+    assert_eq!(manager.estimated_storage_bytes(receipt_index), Some(4));
+
+    manager.append_action_transfer(receipt_index, 100).unwrap();
+    append_weighted_call(&mut manager, receipt_index, 1);
+    let expected = vec![
+        Action::Transfer(TransferAction { deposit: 100 }),
+        Action::FunctionCall(FunctionCallAction {
+            method_name: "method".to_string(),
+            args: vec![],
+            gas: 0,
+            deposit: 0,
+        }),
+    ]
+    .try_to_vec()
+    .unwrap();
+    assert_eq!(manager.estimated_storage_bytes(receipt_index), Some(expected.len() as u64));
+    assert_eq!(manager.estimated_storage_bytes(receipt_index + 1), None);
+}
+
+#[test]
+fn test_action_list_hash() {
+    let mut ext = MockedExternal::new();
+    let mut manager = ReceiptManager::default();
+    let first = create_receipt(&mut manager, &mut ext, "alice.near");
+    let second = create_receipt(&mut manager, &mut ext, "bob.near");
+    let third = create_receipt(&mut manager, &mut ext, "alice.near");
+    for receipt_index in [first, second, third] {
+        manager.append_action_transfer(receipt_index, 100).unwrap();
+    }
+    manager.append_action_transfer(third, 1).unwrap();
+
+    assert_eq!(manager.action_list_hash(first), manager.action_list_hash(second));
+    assert_ne!(manager.action_list_hash(first), manager.action_list_hash(third));
+    assert_eq!(manager.action_list_hash(third + 1), None);
+}
+
+#[test]
+fn test_total_serialized_size() {
+    let mut ext = MockedExternal::new();
+    let mut manager = ReceiptManager::default();
+    let first = create_receipt(&mut manager, &mut ext, "alice.near");
+    let second = create_receipt(&mut manager, &mut ext, "bob.near");
+    manager.append_action_transfer(first, 100).unwrap();
+    append_weighted_call(&mut manager, second, 1);
+    manager.append_action_deploy_contract(second, vec![0; 10]).unwrap();
+
+    let expected = manager
+        .action_receipts
+        .iter()
+        .map(|(_, receipt)| receipt.actions.try_to_vec().unwrap().len() as u64)
+        .sum::<u64>();
+    assert_eq!(manager.total_serialized_size(), expected);
+}
+
+#[test]
+fn test_take_receipts_for() {
+    let mut ext = MockedExternal::new();
+    let mut manager = ReceiptManager::default();
+    let first = create_receipt(&mut manager, &mut ext, "alice.near");
+    let second = create_receipt(&mut manager, &mut ext, "bob.near");
+    let third = create_receipt(&mut manager, &mut ext, "alice.near");
+    manager.append_action_transfer(first, 1).unwrap();
+    append_weighted_call(&mut manager, second, 1);
+    manager.append_action_transfer(third, 3).unwrap();
+
+    let taken = manager.take_receipts_for(&"alice.near".parse().unwrap());
+    let taken_actions = taken
+        .iter()
+        .map(|(receiver, receipt)| (receiver.as_ref(), receipt.actions.clone()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        taken_actions,
+        vec![
+            ("alice.near", vec![Action::Transfer(TransferAction { deposit: 1 })]),
+            ("alice.near", vec![Action::Transfer(TransferAction { deposit: 3 })]),
+        ]
+    );
+    assert_eq!(manager.action_receipts.len(), 1);
+    assert_eq!(manager.action_receipts[0].0.as_ref(), "bob.near");
+
+    assert_eq!(manager.distribute_unused_gas(100), GasDistribution::All);
+    assert_eq!(function_call_gas(&manager, 0), vec![100]);
+}
+
+#[test]
+fn test_normalize_gas_weights() {
+    let mut ext = MockedExternal::new();
+    let mut manager = ReceiptManager::default();
+    let receipt_index = create_receipt(&mut manager, &mut ext, "alice.near");
+    for weight in [4, 8, 12] {
+        append_weighted_call(&mut manager, receipt_index, weight);
+    }
+
+    manager.normalize_gas_weights();
+    assert_eq!(manager.pending_gas_weights(), vec![(0, 0, 1), (0, 1, 2), (0, 2, 3)]);
+
+    assert_eq!(manager.distribute_unused_gas(60), GasDistribution::All);
+    assert_eq!(function_call_gas(&manager, 0), vec![10, 20, 30]);
+}
+
+#[test]
+fn test_check_dependency_cycles() {
+    let mut ext = MockedExternal::new();
+    let mut manager = ReceiptManager::default();
+    let first = create_receipt(&mut manager, &mut ext, "alice.near");
+    let second =
+        manager.create_receipt(&mut ext, vec![first], "bob.near".parse().unwrap()).unwrap();
+    assert_eq!(manager.check_dependency_cycles(), Ok(()));
+
+    // Make the first receipt also wait for the output of the second one.
+    let data_id = CryptoHash::hash_bytes(b"cycle");
+    manager.action_receipts[second as usize]
+        .1
+        .output_data_receivers
+        .push(DataReceiver { data_id, receiver_id: "alice.near".parse().unwrap() });
+    manager.action_receipts[first as usize].1.input_data_ids.push(data_id);
+    assert_eq!(
+        manager.check_dependency_cycles(),
+        Err(VMLogicError::HostError(HostError::ReceiptDependencyCycle { receipt_index: first }))
+    );
+}
+
+#[test]
+fn test_dependents_of() {
+    let mut ext = MockedExternal::new();
+    let mut manager = ReceiptManager::default();
+    let producer = create_receipt(&mut manager, &mut ext, "alice.near");
+    let other = create_receipt(&mut manager, &mut ext, "bob.near");
+    let first =
+        manager.create_receipt(&mut ext, vec![producer], "carol.near".parse().unwrap()).unwrap();
+    manager.create_receipt(&mut ext, vec![other], "dave.near".parse().unwrap()).unwrap();
+    let second = manager
+        .create_receipt(&mut ext, vec![other, producer], "eve.near".parse().unwrap())
+        .unwrap();
+
+    assert_eq!(manager.dependents_of(producer), vec![first, second]);
+    assert!(manager.dependents_of(first).is_empty());
+    assert!(manager.dependents_of(5).is_empty());
+}
+
+#[test]
+fn test_max_dependency_depth() {
+    let mut ext = MockedExternal::new();
+    let mut manager = ReceiptManager::default();
+    assert_eq!(manager.max_dependency_depth(), Ok(0));
+    let first = create_receipt(&mut manager, &mut ext, "alice.near");
+    let second =
+        manager.create_receipt(&mut ext, vec![first], "bob.near".parse().unwrap()).unwrap();
+    manager.create_receipt(&mut ext, vec![second], "carol.near".parse().unwrap()).unwrap();
+    create_receipt(&mut manager, &mut ext, "dave.near");
+    assert_eq!(manager.max_dependency_depth(), Ok(3));
+
+    let data_id = CryptoHash::hash_bytes(b"cycle");
+    manager.action_receipts[second as usize]
+        .1
+        .output_data_receivers
+        .push(DataReceiver { data_id, receiver_id: "alice.near".parse().unwrap() });
+    manager.action_receipts[first as usize].1.input_data_ids.push(data_id);
+    assert_eq!(
+        manager.max_dependency_depth(),
+        Err(HostError::ReceiptDependencyCycle { receipt_index: first }.into())
+    );
+}
+
+#[test]
+fn test_forbidden_receiver() {
+    let mut ext = MockedExternal::new();
+    let mut manager = ReceiptManager::default();
+    let first = create_receipt(&mut manager, &mut ext, "alice.near");
+    let unrestricted = manager.clone();
+    manager.set_forbidden_receiver(|receiver_id| receiver_id.as_ref() == "bob.near");
+    // The predicate is not compared.
+    assert!(manager == unrestricted);
+
+    assert_eq!(
+        manager.create_receipt(&mut ext, vec![first], "bob.near".parse().unwrap()),
+        Err(HostError::InvalidReceiverId { account_id: "bob.near".to_string() }.into())
+    );
+    assert!(manager.action_receipts[0].1.output_data_receivers.is_empty());
+    create_receipt(&mut manager, &mut ext, "carol.near");
+}
+
+#[test]
+fn test_append_action_function_call_typed() {
+    let mut ext = MockedExternal::new();
+    let mut manager = ReceiptManager::default();
+    let receipt_index = create_receipt(&mut manager, &mut ext, "alice.near");
+    let args = (7u32, "bob.near".to_string(), vec![1u64, 2]);
+
+    manager.append_action_function_call_typed(receipt_index, "method", &args, 1, 10).unwrap();
+    assert_eq!(
+        manager.get_action(receipt_index, 0),
+        Some(&Action::FunctionCall(FunctionCallAction {
+            method_name: "method".to_string(),
+            args: args.try_to_vec().unwrap(),
+            gas: 10,
+            deposit: 1,
+        }))
+    );
+    assert!(!manager.has_distributable_weights());
+}
+
+#[test]
+fn test_append_action_from_spec() {
+    let mut ext = MockedExternal::new();
+    let mut manager = ReceiptManager::default();
+    let receipt_index = create_receipt(&mut manager, &mut ext, "alice.near");
+    let public_key = PublicKey::empty(KeyType::ED25519);
+    let key_bytes = public_key.try_to_vec().unwrap();
+
+    let cases = vec![
+        (ActionSpec::CreateAccount, Action::CreateAccount(CreateAccountAction {})),
+        (
+            ActionSpec::DeployContract { code: vec![1, 2] },
+            Action::DeployContract(DeployContractAction { code: vec![1, 2] }),
+        ),
+        (
+            ActionSpec::FunctionCall {
+                method_name: "method".to_string(),
+                args: b"{}".to_vec(),
+                attached_deposit: 1,
+                prepaid_gas: 10,
+                gas_weight: 1,
+            },
+            Action::FunctionCall(FunctionCallAction {
+                method_name: "method".to_string(),
+                args: b"{}".to_vec(),
+                gas: 10,
+                deposit: 1,
+            }),
+        ),
+        (ActionSpec::Transfer { deposit: 2 }, Action::Transfer(TransferAction { deposit: 2 })),
+        (
+            ActionSpec::Stake { stake: 3, public_key: key_bytes.clone() },
+            Action::Stake(StakeAction { stake: 3, public_key: public_key.clone() }),
+        ),
+        (
+            ActionSpec::AddKeyWithFullAccess { public_key: key_bytes.clone(), nonce: 4 },
+            Action::AddKey(AddKeyAction {
+                public_key: public_key.clone(),
+                access_key: AccessKey { nonce: 4, permission: AccessKeyPermission::FullAccess },
+            }),
+        ),
+        (
+            ActionSpec::AddKeyWithFunctionCall {
+                public_key: key_bytes.clone(),
+                nonce: 5,
+                allowance: Some(6),
+                receiver_id: "bob.near".parse().unwrap(),
+                method_names: vec!["foo".to_string()],
+            },
+            Action::AddKey(AddKeyAction {
+                public_key: public_key.clone(),
+                access_key: AccessKey {
+                    nonce: 5,
+                    permission: AccessKeyPermission::FunctionCall(FunctionCallPermission {
+                        allowance: Some(6),
+                        receiver_id: "bob.near".to_string(),
+                        method_names: vec!["foo".to_string()],
+                    }),
+                },
+            }),
+        ),
+        (
+            ActionSpec::DeleteKey { public_key: key_bytes },
+            Action::DeleteKey(DeleteKeyAction { public_key }),
+        ),
+        (
+            ActionSpec::DeleteAccount { beneficiary_id: "bob.near".parse().unwrap() },
+            Action::DeleteAccount(DeleteAccountAction {
+                beneficiary_id: "bob.near".parse().unwrap(),
+            }),
+        ),
+    ];
+    for (expected_index, (spec, expected)) in cases.into_iter().enumerate() {
+        let spec: ActionSpec =
+            serde_json::from_value(serde_json::to_value(&spec).unwrap()).unwrap();
+        let action_index = manager.append_action_from_spec(receipt_index, spec).unwrap();
+        assert_eq!(action_index, expected_index);
+        assert_eq!(manager.get_action(receipt_index, action_index), Some(&expected));
+    }
+    assert_eq!(manager.pending_gas_weights(), vec![(0, 2, 1)]);
+    assert_eq!(
+        manager.append_action_from_spec(1, ActionSpec::CreateAccount),
+        Err(HostError::InvalidReceiptIndex { receipt_index: 1 }.into())
+    );
+}
+
+#[test]
+fn test_get_action() {
+    let mut ext = MockedExternal::new();
+    let mut manager = ReceiptManager::default();
+    let receipt_index = create_receipt(&mut manager, &mut ext, "alice.near");
+    manager.append_action_transfer(receipt_index, 1).unwrap();
+    append_weighted_call(&mut manager, receipt_index, 1);
+
+    let (weighted_receipt, weighted_action, _) = manager.pending_gas_weights()[0];
+    assert!(matches!(
+        manager.get_action(weighted_receipt, weighted_action as usize),
+        Some(Action::FunctionCall(FunctionCallAction { method_name, .. })) if method_name == "method"
+    ));
+    assert_eq!(manager.get_action(receipt_index, 2), None);
+    assert_eq!(manager.get_action(1, 0), None);
+}
+
+#[test]
+fn test_set_receiver() {
+    let mut ext = MockedExternal::new();
+    let mut manager = ReceiptManager::default();
+    let first = create_receipt(&mut manager, &mut ext, "alice.near");
+    let second = create_receipt(&mut manager, &mut ext, "bob.near");
+    let third = manager
+        .create_receipt(&mut ext, vec![first, second], "carol.near".parse().unwrap())
+        .unwrap();
+    manager.create_receipt(&mut ext, vec![third], "dave.near".parse().unwrap()).unwrap();
+
+    manager.set_receiver(third, "eve.near".parse().unwrap()).unwrap();
+    assert_eq!(manager.last_receipt().unwrap().1.as_ref(), "dave.near");
+    assert_eq!(manager.action_receipts[third as usize].0.as_ref(), "eve.near");
+    for receipt_index in [first, second] {
+        let receivers = manager.output_data_receivers(receipt_index).unwrap();
+        assert_eq!(receivers.len(), 1);
+        assert_eq!(receivers[0].receiver_id.as_ref(), "eve.near");
+    }
+    assert_eq!(manager.output_data_receivers(third).unwrap()[0].receiver_id.as_ref(), "dave.near");
+    assert_eq!(
+        manager.set_receiver(4, "eve.near".parse().unwrap()),
+        Err(HostError::InvalidReceiptIndex { receipt_index: 4 }.into())
+    );
+}
+
+#[test]
+fn test_attached_deposit_overflow() {
+    let mut ext = MockedExternal::new();
+    let mut manager = ReceiptManager::default();
+    let first = create_receipt(&mut manager, &mut ext, "alice.near");
+    let second = create_receipt(&mut manager, &mut ext, "bob.near");
+
+    manager.append_action_transfer(first, u128::MAX - 1).unwrap();
+    assert_eq!(
+        manager.append_action_transfer(second, u128::MAX - 1),
+        Err(VMLogicError::HostError(HostError::BalanceExceeded))
+    );
+    assert!(manager.action_receipts[1].1.actions.is_empty());
+}
+
+#[test]
+fn test_attached_deposit_limit() {
+    let mut ext = MockedExternal::new();
+    let mut manager = ReceiptManager::default();
+    manager.set_deposit_limit(100);
+    let receipt_index = create_receipt(&mut manager, &mut ext, "alice.near");
+
+    manager.append_action_transfer(receipt_index, 60).unwrap();
+    assert_eq!(
+        manager.append_action_function_call_weight(
+            receipt_index,
+            b"method".to_vec(),
+            vec![],
+            41,
+            0,
+            GasWeight(0),
+        ),
+        Err(VMLogicError::HostError(HostError::BalanceExceeded))
+    );
+    manager.append_action_transfer(receipt_index, 40).unwrap();
+    assert_eq!(manager.action_receipts[0].1.actions.len(), 2);
+}
+
+#[test]
+fn test_receipt_builder() {
+    let mut ext = MockedExternal::new();
+    let mut manager = ReceiptManager::default();
+    let receipt_index = create_receipt(&mut manager, &mut ext, "alice.near");
+
+    let built = ReceiptBuilder::new(&mut manager, receipt_index)
+        .create_account()
+        .transfer(100)
+        .function_call(b"method".to_vec(), b"{}".to_vec(), 1, 10, GasWeight(0))
+        .delete_account("bob.near".parse().unwrap())
+        .build();
+    assert_eq!(built, Ok(receipt_index));
+    assert_eq!(
+        manager.action_receipts[0].1.actions,
+        vec![
+            Action::CreateAccount(CreateAccountAction {}),
+            Action::Transfer(TransferAction { deposit: 100 }),
+            Action::FunctionCall(FunctionCallAction {
+                method_name: "method".to_string(),
+                args: b"{}".to_vec(),
+                gas: 10,
+                deposit: 1,
+            }),
+            Action::DeleteAccount(DeleteAccountAction {
+                beneficiary_id: "bob.near".parse().unwrap()
+            }),
+        ]
+    );
+}
+
+#[test]
+fn test_receipt_builder_stops_at_first_error() {
+    let mut ext = MockedExternal::new();
+    let mut manager = ReceiptManager::default();
+    let receipt_index = create_receipt(&mut manager, &mut ext, "alice.near");
+
+    let built = ReceiptBuilder::new(&mut manager, receipt_index)
+        .transfer(1)
+        .function_call(vec![0xff], vec![], 0, 0, GasWeight(0))
+        .transfer(2)
+        .build();
+    assert_eq!(
+        built,
+        Err(VMLogicError::HostError(HostError::InvalidMethodNameUtf8 { valid_up_to: 0 }))
+    );
+    assert_eq!(
+        manager.action_receipts[0].1.actions,
+        vec![Action::Transfer(TransferAction { deposit: 1 })]
+    );
+}
+
+#[test]
+fn test_add_key_with_function_call_rejects_zero_allowance() {
+    let mut ext = MockedExternal::new();
+    let mut manager = ReceiptManager::default();
+    let receipt_index = create_receipt(&mut manager, &mut ext, "alice.near");
+    let public_key = PublicKey::empty(KeyType::ED25519).try_to_vec().unwrap();
+    let mut add_key = |allowance| {
+        manager.append_action_add_key_with_function_call(
+            receipt_index,
+            public_key.clone(),
+            0,
+            allowance,
+            "bob.near".parse().unwrap(),
+            vec![],
+        )
+    };
+
+    assert_eq!(add_key(Some(0)), Err(VMLogicError::HostError(HostError::ZeroAllowance)));
+    assert_eq!(add_key(Some(1)), Ok(()));
+    assert_eq!(add_key(None), Ok(()));
+    assert_eq!(manager.action_receipts[0].1.actions.len(), 2);
+}
+
+#[test]
+fn test_reject_zero_stake() {
+    let mut ext = MockedExternal::new();
+    let mut manager = ReceiptManager::default();
+    let receipt_index = create_receipt(&mut manager, &mut ext, "alice.near");
+    let public_key = PublicKey::empty(KeyType::ED25519).try_to_vec().unwrap();
+    manager.append_action_stake(receipt_index, 0, public_key.clone()).unwrap();
+
+    manager.set_reject_zero_stake(true);
+    assert_eq!(
+        manager.append_action_stake(receipt_index, 0, public_key.clone()),
+        Err(HostError::ZeroStake.into())
+    );
+    manager.append_action_stake(receipt_index, 1, public_key.clone()).unwrap();
+    ReceiptBuilder::new(&mut manager, receipt_index).unstake(public_key).build().unwrap();
+    assert_eq!(manager.total_action_count(), 3);
+}
+
+#[test]
+fn test_reject_duplicate_key_nonces() {
+    let mut ext = MockedExternal::new();
+    let mut manager = ReceiptManager::default();
+    let first = create_receipt(&mut manager, &mut ext, "alice.near");
+    let second = create_receipt(&mut manager, &mut ext, "bob.near");
+    let public_key = PublicKey::empty(KeyType::ED25519).try_to_vec().unwrap();
+
+    manager.append_action_add_key_with_full_access(first, public_key.clone(), 7).unwrap();
+    manager.append_action_add_key_with_full_access(first, public_key.clone(), 7).unwrap();
+
+    manager.set_reject_duplicate_key_nonces(true);
+    manager.append_action_add_key_with_full_access(first, public_key.clone(), 8).unwrap();
+    assert_eq!(
+        manager.append_action_add_key_with_function_call(
+            second,
+            public_key,
+            8,
+            None,
+            "carol.near".parse().unwrap(),
+            vec![],
+        ),
+        Err(HostError::DuplicateAccessKeyNonce { nonce: 8 }.into())
+    );
+    assert_eq!(manager.total_action_count(), 3);
+}
+
+#[test]
+fn test_reject_duplicate_method_names() {
+    let mut ext = MockedExternal::new();
+    let mut manager = ReceiptManager::default();
+    let receipt_index = create_receipt(&mut manager, &mut ext, "alice.near");
+    let public_key = PublicKey::empty(KeyType::ED25519).try_to_vec().unwrap();
+    let add_key = |manager: &mut ReceiptManager, nonce| {
+        manager.append_action_add_key_with_function_call(
+            receipt_index,
+            public_key.clone(),
+            nonce,
+            None,
+            "bob.near".parse().unwrap(),
+            vec![b"foo".to_vec(), b"bar".to_vec(), b"foo".to_vec()],
+        )
+    };
+
+    add_key(&mut manager, 1).unwrap();
+    manager.set_reject_duplicate_method_names(true);
+    assert_eq!(
+        add_key(&mut manager, 2),
+        Err(HostError::DuplicateMethodName { method_name: "foo".to_string() }.into())
+    );
+    assert_eq!(manager.total_action_count(), 1);
+}
+
+#[test]
+fn test_distinct_receivers() {
+    let mut ext = MockedExternal::new();
+    let mut manager = ReceiptManager::default();
+    assert!(manager.distinct_receivers().is_empty());
+    for receiver_id in ["bob.near", "alice.near", "bob.near"] {
+        create_receipt(&mut manager, &mut ext, receiver_id);
+    }
+
+    let expected: Vec<AccountId> = vec!["bob.near".parse().unwrap(), "alice.near".parse().unwrap()];
+    assert_eq!(manager.distinct_receivers(), expected);
+}
+
+#[test]
+fn test_borsh_round_trip() {
+    let mut ext = MockedExternal::new();
+    let mut manager = ReceiptManager::with_gas_price(10);
+    manager.set_deposit_limit(1_000);
+    manager.set_max_total_actions(10);
+    let first = create_receipt(&mut manager, &mut ext, "alice.near");
+    let second =
+        manager.create_receipt(&mut ext, vec![first], "bob.near".parse().unwrap()).unwrap();
+    manager.append_action_transfer(first, 100).unwrap();
+    append_weighted_call(&mut manager, second, 3);
+    assert!(manager.set_max_gas_per_action(second, 0, 50));
+
+    let bytes = manager.try_to_vec().unwrap();
+    let mut restored = ReceiptManager::try_from_slice(&bytes).unwrap();
+    assert!(restored == manager);
+    assert_eq!(restored.pending_gas_weights(), vec![(1, 0, 3)]);
+    assert_eq!(restored.distribute_unused_gas(100), GasDistribution::Partial { undistributed: 50 });
+    assert_eq!(function_call_gas(&restored, 1), vec![50]);
+}
+
+#[cfg(feature = "test_features")]
+#[test]
+fn test_to_json() {
+    let mut ext = MockedExternal::new();
+    let mut manager = ReceiptManager::default();
+    let first = create_receipt(&mut manager, &mut ext, "alice.near");
+    let second =
+        manager.create_receipt(&mut ext, vec![first], "bob.near".parse().unwrap()).unwrap();
+    manager.append_action_transfer(first, 1).unwrap();
+    append_weighted_call(&mut manager, second, 1);
+    let data_id = manager.input_data_ids(second).unwrap()[0].to_string();
+
+    let expected = serde_json::json!([
+        {
+            "receiver_id": "alice.near",
+            "input_data_ids": [],
+            "output_data_receivers": [{ "data_id": data_id, "receiver_id": "bob.near" }],
+            "actions": [{ "Transfer": { "deposit": "1" } }],
+        },
+        {
+            "receiver_id": "bob.near",
+            "input_data_ids": [data_id],
+            "output_data_receivers": [],
+            "actions": [{
+                "FunctionCall": { "method_name": "method", "args": "", "gas": 0, "deposit": "0" }
+            }],
+        },
+    ]);
+    assert_eq!(manager.to_json(), expected);
+}
+
+#[test]
+fn test_clear() {
+    let mut ext = MockedExternal::new();
+    let mut manager = ReceiptManager::default();
+    manager.set_deposit_limit(10);
+    let first = create_receipt(&mut manager, &mut ext, "alice.near");
+    let second = create_receipt(&mut manager, &mut ext, "bob.near");
+    manager.append_action_transfer(first, 10).unwrap();
+    append_weighted_call(&mut manager, second, 1);
+    assert_eq!(manager.receipt_count(), 2);
+
+    manager.clear();
+    assert_eq!(manager.receipt_count(), 0);
+    assert_eq!(manager.pending_gas_weights(), vec![]);
+    assert_eq!(manager.take_receipts(), vec![]);
+
+    // The deposit attached before clearing no longer counts towards the limit.
+    let receipt_index = create_receipt(&mut manager, &mut ext, "alice.near");
+    manager.append_action_transfer(receipt_index, 10).unwrap();
+    assert_eq!(
+        manager.append_action_transfer(receipt_index, 1),
+        Err(VMLogicError::HostError(HostError::BalanceExceeded))
+    );
+}
+
+#[test]
+fn test_deploy_contract_size_limit() {
+    let mut ext = MockedExternal::new();
+    let mut manager = ReceiptManager::default();
+    manager.set_max_contract_size(10);
+    let receipt_index = create_receipt(&mut manager, &mut ext, "alice.near");
+
+    assert_eq!(manager.append_action_deploy_contract(receipt_index, vec![0; 10]), Ok(()));
+    assert_eq!(
+        manager.append_action_deploy_contract(receipt_index, vec![0; 11]),
+        Err(VMLogicError::HostError(HostError::ContractSizeExceeded { size: 11, limit: 10 }))
+    );
+    assert_eq!(manager.action_receipts[0].1.actions.len(), 1);
+}
+
+#[test]
+fn test_describe() {
+    let mut ext = MockedExternal::new();
+    let mut manager = ReceiptManager::default();
+    let first = create_receipt(&mut manager, &mut ext, "alice.near");
+    let second =
+        manager.create_receipt(&mut ext, vec![first], "bob.near".parse().unwrap()).unwrap();
+    ReceiptBuilder::new(&mut manager, first).transfer(100).build().unwrap();
+    ReceiptBuilder::new(&mut manager, second)
+        .function_call(b"method".to_vec(), b"{}".to_vec(), 1, 10, GasWeight(0))
+        .build()
+        .unwrap();
+
+    let description = manager.describe();
+    for expected in [
+        "receipt 0 to alice.near: 0 input data dependencies, 1 output data receivers",
+        "  Transfer deposit=100",
+        "receipt 1 to bob.near: 1 input data dependencies, 0 output data receivers",
+        "  FunctionCall method=method args_len=2 gas=10 deposit=1",
+    ] {
+        assert!(description.contains(expected), "{:?} not in {}", expected, description);
+    }
+}
+
+/// [`MockedExternal`] which always generates the same data id.
+struct FixedDataIdExternal(MockedExternal);
+
+impl External for FixedDataIdExternal {
+    fn storage_set(&mut self, key: &[u8], value: &[u8]) -> dependencies::Result<()> {
+        self.0.storage_set(key, value)
+    }
+
+    fn storage_get(&self, key: &[u8]) -> dependencies::Result<Option<Box<dyn ValuePtr + '_>>> {
+        self.0.storage_get(key)
+    }
+
+    fn storage_remove(&mut self, key: &[u8]) -> dependencies::Result<()> {
+        self.0.storage_remove(key)
+    }
+
+    fn storage_remove_subtree(&mut self, prefix: &[u8]) -> dependencies::Result<()> {
+        self.0.storage_remove_subtree(prefix)
+    }
+
+    fn storage_has_key(&mut self, key: &[u8]) -> dependencies::Result<bool> {
+        self.0.storage_has_key(key)
+    }
+
+    fn generate_data_id(&mut self) -> CryptoHash {
+        CryptoHash::hash_bytes(b"data")
+    }
+
+    fn get_trie_nodes_count(&self) -> TrieNodesCount {
+        self.0.get_trie_nodes_count()
+    }
+
+    fn validator_stake(&self, account_id: &AccountId) -> dependencies::Result<Option<Balance>> {
+        self.0.validator_stake(account_id)
+    }
+
+    fn validator_total_stake(&self) -> dependencies::Result<Balance> {
+        self.0.validator_total_stake()
+    }
+}
+
+#[test]
+fn test_create_receipt_rejects_duplicate_data_id() {
+    let mut ext = FixedDataIdExternal(MockedExternal::new());
+    let mut manager = ReceiptManager::default();
+    let first = manager.create_receipt(&mut ext, vec![], "alice.near".parse().unwrap()).unwrap();
+    manager.create_receipt(&mut ext, vec![first], "bob.near".parse().unwrap()).unwrap();
+    assert_eq!(
+        manager.create_receipt(&mut ext, vec![first], "carol.near".parse().unwrap()),
+        Err(VMLogicError::HostError(HostError::DuplicateDataId))
+    );
+    assert_eq!(manager.receipt_count(), 2);
+}
+
+#[test]
+fn test_last_receipt() {
+    let mut ext = MockedExternal::new();
+    let mut manager = ReceiptManager::default();
+    assert_eq!(manager.last_receipt(), None);
+
+    let receiver_id: AccountId = "alice.near".parse().unwrap();
+    let receipt_index = manager.create_receipt(&mut ext, vec![], receiver_id.clone()).unwrap();
+    assert_eq!(manager.last_receipt(), Some((receipt_index, &receiver_id)));
+
+    let receiver_id: AccountId = "bob.near".parse().unwrap();
+    let receipt_index = manager.create_receipt(&mut ext, vec![], receiver_id.clone()).unwrap();
+    assert_eq!(manager.last_receipt(), Some((receipt_index, &receiver_id)));
+}
+
+#[test]
+fn test_pending_gas_weights() {
+    let mut ext = MockedExternal::new();
+    let mut manager = ReceiptManager::default();
+    let first = create_receipt(&mut manager, &mut ext, "alice.near");
+    let second = create_receipt(&mut manager, &mut ext, "bob.near");
+    append_weighted_call(&mut manager, first, 2);
+    manager.append_action_transfer(second, 1).unwrap();
+    append_weighted_call(&mut manager, second, 0);
+    append_weighted_call(&mut manager, second, 5);
+
+    assert_eq!(manager.pending_gas_weights(), vec![(0, 0, 2), (1, 2, 5)]);
+}
+
+#[test]
+fn test_distribute_unused_gas_saturates() {
+    let mut ext = MockedExternal::new();
+    let mut manager = ReceiptManager::default();
+    let receipt_index = create_receipt(&mut manager, &mut ext, "alice.near");
+    for prepaid_gas in [Gas::MAX - 10, 0] {
+        manager
+            .append_action_function_call_weight(
+                receipt_index,
+                b"method".to_vec(),
+                vec![],
+                0,
+                prepaid_gas,
+                GasWeight(1),
+            )
+            .unwrap();
+    }
+
+    assert_eq!(manager.distribute_unused_gas(100), GasDistribution::Saturated);
+    assert_eq!(function_call_gas(&manager, 0), vec![Gas::MAX, 50]);
+}
+
+#[test]
+fn test_input_data_ids() {
+    let mut ext = MockedExternal::new();
+    let mut manager = ReceiptManager::default();
+    let first = create_receipt(&mut manager, &mut ext, "alice.near");
+    let second = create_receipt(&mut manager, &mut ext, "bob.near");
+    let third = manager
+        .create_receipt(&mut ext, vec![first, second], "carol.near".parse().unwrap())
+        .unwrap();
+
+    let input_data_ids = manager.input_data_ids(third).unwrap();
+    assert_eq!(input_data_ids.len(), 2);
+    assert_eq!(input_data_ids[0], manager.action_receipts[0].1.output_data_receivers[0].data_id);
+    assert_eq!(input_data_ids[1], manager.action_receipts[1].1.output_data_receivers[0].data_id);
+    assert_eq!(manager.input_data_ids(first), Some(&[][..]));
+    assert_eq!(manager.input_data_ids(third + 1), None);
+}
+
+#[test]
+fn test_output_data_receivers() {
+    let mut ext = MockedExternal::new();
+    let mut manager = ReceiptManager::default();
+    let first = create_receipt(&mut manager, &mut ext, "alice.near");
+    assert_eq!(manager.output_data_receivers(first), Some(&[][..]));
+
+    let second =
+        manager.create_receipt(&mut ext, vec![first], "bob.near".parse().unwrap()).unwrap();
+    let receivers = manager.output_data_receivers(first).unwrap();
+    assert_eq!(receivers.len(), 1);
+    assert_eq!(receivers[0].receiver_id.as_ref(), "bob.near");
+    assert_eq!(manager.input_data_ids(second), Some(&[receivers[0].data_id][..]));
+    assert_eq!(manager.output_data_receivers(second + 1), None);
+}
+
+#[test]
+fn test_reserve_receipts() {
+    let mut ext = MockedExternal::new();
+    let mut manager = ReceiptManager::default();
+    create_receipt(&mut manager, &mut ext, "alice.near");
+
+    manager.reserve_receipts(100);
+    assert!(manager.action_receipts.capacity() >= 101);
+    assert_eq!(manager.receipt_count(), 1);
+}
+
+#[test]
+fn test_validate_all() {
+    let mut ext = MockedExternal::new();
+    let mut manager = ReceiptManager::default();
+    let first = create_receipt(&mut manager, &mut ext, "alice.near");
+    let second =
+        manager.create_receipt(&mut ext, vec![first], "bob.near".parse().unwrap()).unwrap();
+    ReceiptBuilder::new(&mut manager, first)
+        .deploy_contract(vec![0; 10])
+        .transfer(100)
+        .build()
+        .unwrap();
+    ReceiptBuilder::new(&mut manager, second)
+        .function_call(b"method".to_vec(), vec![], 10, Gas::MAX / 2, GasWeight(0))
+        .build()
+        .unwrap();
+    assert_eq!(manager.validate_all(), Ok(()));
+
+    let mut invalid = manager.clone();
+    invalid.set_max_contract_size(9);
+    assert_eq!(
+        invalid.validate_all(),
+        Err(VMLogicError::HostError(HostError::ContractSizeExceeded { size: 10, limit: 9 }))
+    );
+
+    let mut invalid = manager.clone();
+    invalid.set_deposit_limit(109);
+    assert_eq!(invalid.validate_all(), Err(VMLogicError::HostError(HostError::BalanceExceeded)));
+
+    let mut invalid = manager.clone();
+    invalid.action_receipts[1].1.actions.push(Action::FunctionCall(FunctionCallAction {
+        method_name: "method".to_string(),
+        args: vec![],
+        gas: Gas::MAX / 2 + 2,
+        deposit: 0,
+    }));
+    assert_eq!(invalid.validate_all(), Err(VMLogicError::HostError(HostError::IntegerOverflow)));
+
+    let mut invalid = manager.clone();
+    invalid.action_receipts[1].1.actions.push(Action::AddKey(AddKeyAction {
+        public_key: PublicKey::empty(KeyType::ED25519),
+        access_key: AccessKey {
+            nonce: 0,
+            permission: AccessKeyPermission::FunctionCall(FunctionCallPermission {
+                allowance: Some(0),
+                receiver_id: "carol.near".to_string(),
+                method_names: vec![],
+            }),
+        },
+    }));
+    assert_eq!(invalid.validate_all(), Err(VMLogicError::HostError(HostError::ZeroAllowance)));
+
+    let mut invalid = manager;
+    let data_id = CryptoHash::hash_bytes(b"cycle");
+    invalid.action_receipts[1]
+        .1
+        .output_data_receivers
+        .push(DataReceiver { data_id, receiver_id: "alice.near".parse().unwrap() });
+    invalid.action_receipts[0].1.input_data_ids.push(data_id);
+    assert_eq!(
+        invalid.validate_all(),
+        Err(VMLogicError::HostError(HostError::ReceiptDependencyCycle { receipt_index: first }))
+    );
+}
+
+#[test]
+fn test_append_action_function_call_str() {
+    let mut ext = MockedExternal::new();
+    let mut bytes_manager = ReceiptManager::default();
+    let mut str_manager = ReceiptManager::default();
+    let bytes_index = create_receipt(&mut bytes_manager, &mut ext, "alice.near");
+    let str_index = create_receipt(&mut str_manager, &mut ext, "alice.near");
+
+    bytes_manager
+        .append_action_function_call_weight(
+            bytes_index,
+            b"method".to_vec(),
+            b"{}".to_vec(),
+            1,
+            10,
+            GasWeight(2),
+        )
+        .unwrap();
+    str_manager
+        .append_action_function_call_str(
+            str_index,
+            "method".to_string(),
+            b"{}".to_vec(),
+            1,
+            10,
+            GasWeight(2),
+            false,
+        )
+        .unwrap();
+    assert_eq!(bytes_manager.action_receipts, str_manager.action_receipts);
+    assert_eq!(bytes_manager.pending_gas_weights(), str_manager.pending_gas_weights());
+}
+
+#[test]
+fn test_receipt_index_for_data_id() {
+    let mut ext = MockedExternal::new();
+    let mut manager = ReceiptManager::default();
+    let first = create_receipt(&mut manager, &mut ext, "alice.near");
+    let second = create_receipt(&mut manager, &mut ext, "bob.near");
+    let third = manager
+        .create_receipt(&mut ext, vec![first, second], "carol.near".parse().unwrap())
+        .unwrap();
+
+    let input_data_ids = manager.input_data_ids(third).unwrap().to_vec();
+    assert_eq!(manager.receipt_index_for_data_id(&input_data_ids[0]), Some(first));
+    assert_eq!(manager.receipt_index_for_data_id(&input_data_ids[1]), Some(second));
+    assert_eq!(manager.receipt_index_for_data_id(&CryptoHash::default()), None);
+}
+
+#[test]
+fn test_sort_actions_on_take() {
+    let mut ext = MockedExternal::new();
+    let append_actions = |manager: &mut ReceiptManager, ext: &mut MockedExternal| {
+        let receipt_index = create_receipt(manager, ext, "alice.near");
+        manager.append_action_transfer(receipt_index, 1).unwrap();
+        manager.append_action_delete_account(receipt_index, "bob.near".parse().unwrap()).unwrap();
+        manager.append_action_transfer(receipt_index, 2).unwrap();
+        manager.append_action_create_account(receipt_index).unwrap();
+    };
+    let transfer = |deposit| Action::Transfer(TransferAction { deposit });
+    let delete_account =
+        Action::DeleteAccount(DeleteAccountAction { beneficiary_id: "bob.near".parse().unwrap() });
+    let create_account = Action::CreateAccount(CreateAccountAction {});
+
+    let mut manager = ReceiptManager::default();
+    append_actions(&mut manager, &mut ext);
+    assert_eq!(
+        manager.take_receipts()[0].1.actions,
+        vec![transfer(1), delete_account.clone(), transfer(2), create_account.clone()]
+    );
+
+    manager.set_sort_actions_on_take(true);
+    append_actions(&mut manager, &mut ext);
+    assert_eq!(
+        manager.take_receipts()[0].1.actions,
+        vec![create_account, transfer(1), transfer(2), delete_account]
+    );
+}
+
+#[test]
+fn test_take_receipts_indexed() {
+    let mut ext = MockedExternal::new();
+    let mut manager = ReceiptManager::default();
+    let receivers = ["alice.near", "bob.near", "carol.near"];
+    for (deposit, receiver_id) in receivers.iter().enumerate() {
+        let receipt_index = create_receipt(&mut manager, &mut ext, receiver_id);
+        manager.append_action_transfer(receipt_index, deposit as u128).unwrap();
+    }
+
+    let predecessor_id: AccountId = "predecessor.near".parse().unwrap();
+    let signer_id: AccountId = "signer.near".parse().unwrap();
+    let signer_public_key = PublicKey::empty(KeyType::ED25519);
+    let taken = manager.take_receipts_indexed(&predecessor_id, &signer_id, &signer_public_key, 7);
+    assert_eq!(manager.receipt_count(), 0);
+    assert_eq!(taken.len(), receivers.len());
+    for (expected_index, (receipt_index, receipt)) in taken.into_iter().enumerate() {
+        assert_eq!(receipt_index, expected_index as ReceiptIndex);
+        assert_eq!(receipt.predecessor_id, predecessor_id);
+        assert_eq!(receipt.receiver_id.as_ref(), receivers[expected_index]);
+        match receipt.receipt {
+            ReceiptEnum::Action(action_receipt) => {
+                assert_eq!(action_receipt.signer_id, signer_id);
+                assert_eq!(action_receipt.signer_public_key, signer_public_key);
+                assert_eq!(action_receipt.gas_price, 7);
+                assert_eq!(
+                    action_receipt.actions,
+                    vec![Action::Transfer(TransferAction { deposit: expected_index as u128 })]
+                );
+            }
+            ReceiptEnum::Data(_) => panic!("expected an action receipt"),
+        }
+    }
+}
+
+#[test]
+fn test_receipt_gas_price_override() {
+    let mut ext = MockedExternal::new();
+    let mut manager = ReceiptManager::default();
+    let first = create_receipt(&mut manager, &mut ext, "alice.near");
+    let second = create_receipt(&mut manager, &mut ext, "bob.near");
+    let third = create_receipt(&mut manager, &mut ext, "carol.near");
+    manager.set_receipt_gas_price(second, 5).unwrap();
+    manager.set_receipt_gas_price(third, 9).unwrap();
+    assert_eq!(
+        manager.set_receipt_gas_price(3, 1).unwrap_err(),
+        VMLogicError::HostError(HostError::InvalidReceiptIndex { receipt_index: 3 })
+    );
+    // The override of the third receipt follows it to index 1.
+    manager.remove_receipt(first).unwrap();
+
+    let signer_id: AccountId = "signer.near".parse().unwrap();
+    let taken = manager.take_receipts_indexed(
+        &signer_id,
+        &signer_id,
+        &PublicKey::empty(KeyType::ED25519),
+        7,
+    );
+    let gas_prices = taken
+        .into_iter()
+        .map(|(_, receipt)| match receipt.receipt {
+            ReceiptEnum::Action(action_receipt) => action_receipt.gas_price,
+            ReceiptEnum::Data(_) => panic!("expected an action receipt"),
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(gas_prices, vec![5, 9]);
+
+    // Overrides are dropped along with the taken receipts.
+    create_receipt(&mut manager, &mut ext, "alice.near");
+    let taken = manager.take_receipts_indexed(
+        &signer_id,
+        &signer_id,
+        &PublicKey::empty(KeyType::ED25519),
+        7,
+    );
+    match &taken[0].1.receipt {
+        ReceiptEnum::Action(action_receipt) => assert_eq!(action_receipt.gas_price, 7),
+        ReceiptEnum::Data(_) => panic!("expected an action receipt"),
+    }
+
+    // Receipts taken without being turned into `Receipt`s keep their override.
+    let receipt_index = create_receipt(&mut manager, &mut ext, "alice.near");
+    manager.set_receipt_gas_price(receipt_index, 3).unwrap();
+    let taken = manager.take_receipts_for(&"alice.near".parse().unwrap());
+    assert_eq!(taken[0].1.gas_price, Some(3));
+}
+
+#[derive(Default)]
+struct CountingMetrics {
+    receipts: Mutex<Vec<usize>>,
+    actions: Mutex<Vec<ReceiptIndex>>,
+    distributions: Mutex<Vec<(Gas, usize)>>,
+}
+
+impl ReceiptManagerMetrics for CountingMetrics {
+    fn receipt_created(&self, num_dependencies: usize) {
+        self.receipts.lock().unwrap().push(num_dependencies);
+    }
+
+    fn action_appended(&self, receipt_index: ReceiptIndex) {
+        self.actions.lock().unwrap().push(receipt_index);
+    }
+
+    fn unused_gas_distributed(&self, unused_gas: Gas, num_function_calls: usize) {
+        self.distributions.lock().unwrap().push((unused_gas, num_function_calls));
+    }
+}
+
+#[test]
+fn test_metrics() {
+    let mut ext = MockedExternal::new();
+    let metrics = Arc::new(CountingMetrics::default());
+    let mut manager = ReceiptManager::default();
+    manager.set_metrics(metrics.clone());
+
+    let first = create_receipt(&mut manager, &mut ext, "alice.near");
+    let second =
+        manager.create_receipt(&mut ext, vec![first], "bob.near".parse().unwrap()).unwrap();
+    manager.append_action_transfer(first, 1).unwrap();
+    append_weighted_call(&mut manager, second, 1);
+    append_weighted_call(&mut manager, second, 2);
+    assert_eq!(manager.distribute_unused_gas(30), GasDistribution::All);
+
+    assert_eq!(*metrics.receipts.lock().unwrap(), vec![0, 1]);
+    assert_eq!(*metrics.actions.lock().unwrap(), vec![first, second, second]);
+    assert_eq!(*metrics.distributions.lock().unwrap(), vec![(30, 2)]);
+}
+
+#[test]
+fn test_on_receipt_ready() {
+    let mut ext = MockedExternal::new();
+    let ready = Arc::new(Mutex::new(vec![]));
+    let mut manager = ReceiptManager::default();
+    let ready_clone = ready.clone();
+    manager.set_on_receipt_ready(
+        2,
+        Arc::new(move |receipt_index| ready_clone.lock().unwrap().push(receipt_index)),
+    );
+
+    let first = create_receipt(&mut manager, &mut ext, "alice.near");
+    let second = create_receipt(&mut manager, &mut ext, "bob.near");
+    manager.append_action_transfer(first, 1).unwrap();
+    manager.append_action_transfer(second, 1).unwrap();
+    assert!(ready.lock().unwrap().is_empty());
+    manager.insert_action(second, 0, Action::CreateAccount(CreateAccountAction {})).unwrap();
+    append_weighted_call(&mut manager, first, 1);
+    append_weighted_call(&mut manager, first, 1);
+    assert_eq!(*ready.lock().unwrap(), vec![second, first]);
+}
+
+#[test]
+fn test_construction_gas_hook() {
+    let mut ext = MockedExternal::new();
+    let mut manager = ReceiptManager::default();
+    let costs = Arc::new(Mutex::new(vec![]));
+    let costs_clone = Arc::clone(&costs);
+    manager.set_construction_gas_hook(
+        RuntimeFeesConfig::test(),
+        Arc::new(move |gas| costs_clone.lock().unwrap().push(gas)),
+    );
+
+    let receipt_index = create_receipt(&mut manager, &mut ext, "alice.near");
+    manager.append_action_deploy_contract(receipt_index, vec![0; 10]).unwrap();
+    manager.append_action_deploy_contract(receipt_index, vec![0; 1000]).unwrap();
+    let costs = costs.lock().unwrap();
+    assert_eq!(costs.len(), 3);
+    assert!(costs.iter().all(|&gas| gas > 0));
+    assert!(costs[2] > costs[1]);
+}
+
+#[test]
+fn test_set_gas_weight() {
+    let mut ext = MockedExternal::new();
+    let mut manager = ReceiptManager::default();
+    let receipt_index = create_receipt(&mut manager, &mut ext, "alice.near");
+    append_weighted_call(&mut manager, receipt_index, 0);
+    append_weighted_call(&mut manager, receipt_index, 1);
+    manager.append_action_transfer(receipt_index, 1).unwrap();
+
+    manager.set_gas_weight(receipt_index, 0, GasWeight(3)).unwrap();
+    assert_eq!(manager.pending_gas_weights(), vec![(0, 1, 1), (0, 0, 3)]);
+    assert_eq!(
+        manager.set_gas_weight(receipt_index, 2, GasWeight(1)),
+        Err(HostError::InvalidActionIndex { action_index: 2 }.into())
+    );
+    assert_eq!(
+        manager.set_gas_weight(receipt_index + 1, 0, GasWeight(1)),
+        Err(HostError::InvalidReceiptIndex { receipt_index: receipt_index + 1 }.into())
+    );
+
+    assert_eq!(manager.distribute_unused_gas(100), GasDistribution::All);
+    assert_eq!(function_call_gas(&manager, 0), vec![75, 25]);
+}
+
+#[test]
+fn test_clear_gas_weight() {
+    let mut ext = MockedExternal::new();
+    let mut manager = ReceiptManager::default();
+    let receipt_index = create_receipt(&mut manager, &mut ext, "alice.near");
+    append_weighted_call(&mut manager, receipt_index, 1);
+    append_weighted_call(&mut manager, receipt_index, 1);
+
+    assert!(manager.clear_gas_weight(receipt_index, 0));
+    assert!(!manager.clear_gas_weight(receipt_index, 0));
+    assert_eq!(manager.distribute_unused_gas(100), GasDistribution::All);
+    assert_eq!(function_call_gas(&manager, 0), vec![0, 100]);
+}
+
+#[test]
+fn test_min_gas_per_action() {
+    let mut ext = MockedExternal::new();
+    let mut manager = ReceiptManager::default();
+    let receipt_index = create_receipt(&mut manager, &mut ext, "alice.near");
+    for weight in [1, 1, 2] {
+        append_weighted_call(&mut manager, receipt_index, weight);
+    }
+    manager.append_action_transfer(receipt_index, 1).unwrap();
+
+    assert!(manager.set_min_gas_per_action(receipt_index, 0, 40));
+    assert!(!manager.set_min_gas_per_action(receipt_index, 3, 40));
+    assert_eq!(manager.distribute_unused_gas(100), GasDistribution::All);
+    // The first action receives its minimum plus its share of the remaining 60.
+    assert_eq!(function_call_gas(&manager, 0), vec![55, 15, 30]);
+}
+
+#[test]
+fn test_min_gas_per_action_not_covered() {
+    let mut ext = MockedExternal::new();
+    let mut manager = ReceiptManager::default();
+    let receipt_index = create_receipt(&mut manager, &mut ext, "alice.near");
+    for weight in [1, 1, 2] {
+        append_weighted_call(&mut manager, receipt_index, weight);
+    }
+
+    assert!(manager.set_min_gas_per_action(receipt_index, 0, 60));
+    assert!(manager.set_min_gas_per_action(receipt_index, 1, 40));
+    assert_eq!(manager.distribute_unused_gas(50), GasDistribution::MinGasNotCovered);
+    assert_eq!(function_call_gas(&manager, 0), vec![30, 20, 0]);
+}
+
+#[test]
+fn test_distribute_unused_gas_keep_weights() {
+    let mut ext = MockedExternal::new();
+    let mut manager = ReceiptManager::default();
+    let receipt_index = create_receipt(&mut manager, &mut ext, "alice.near");
+    append_weighted_call(&mut manager, receipt_index, 1);
+    append_weighted_call(&mut manager, receipt_index, 3);
+
+    assert_eq!(manager.distribute_unused_gas_keep_weights(100), GasDistribution::All);
+    assert_eq!(function_call_gas(&manager, 0), vec![25, 75]);
+    assert_eq!(manager.pending_gas_weights(), vec![(0, 0, 1), (0, 1, 3)]);
+
+    assert_eq!(manager.distribute_unused_gas(100), GasDistribution::All);
+    assert_eq!(function_call_gas(&manager, 0), vec![50, 150]);
+    assert!(manager.pending_gas_weights().is_empty());
+}
+
+#[test]
+fn test_max_gas_per_action() {
+    let mut ext = MockedExternal::new();
+    let mut manager = ReceiptManager::default();
+    let receipt_index = create_receipt(&mut manager, &mut ext, "alice.near");
+    for weight in [1, 1, 2] {
+        append_weighted_call(&mut manager, receipt_index, weight);
+    }
+    manager.append_action_transfer(receipt_index, 1).unwrap();
+
+    assert!(manager.set_max_gas_per_action(receipt_index, 0, 10));
+    assert!(!manager.set_max_gas_per_action(receipt_index, 3, 10));
+    assert_eq!(manager.distribute_unused_gas(100), GasDistribution::All);
+    // Without the ceiling the first action would receive 25.
+    assert_eq!(function_call_gas(&manager, 0), vec![10, 30, 60]);
+}
+
+#[test]
+fn test_max_gas_per_action_leaves_gas_unused() {
+    let mut ext = MockedExternal::new();
+    let mut manager = ReceiptManager::default();
+    let receipt_index = create_receipt(&mut manager, &mut ext, "alice.near");
+    append_weighted_call(&mut manager, receipt_index, 1);
+    append_weighted_call(&mut manager, receipt_index, 1);
+
+    assert!(manager.set_max_gas_per_action(receipt_index, 0, 10));
+    assert!(manager.set_max_gas_per_action(receipt_index, 1, 20));
+    assert_eq!(manager.distribute_unused_gas(100), GasDistribution::Partial { undistributed: 70 });
+    assert_eq!(function_call_gas(&manager, 0), vec![10, 20]);
+}
+
+#[test]
+fn test_distribute_respects_gas_floor() {
+    let mut ext = MockedExternal::new();
+    let mut manager = ReceiptManager::default();
+    let receipt_index = create_receipt(&mut manager, &mut ext, "alice.near");
+    for prepaid_gas in [70, 10] {
+        manager
+            .append_action_function_call_str(
+                receipt_index,
+                "method".to_string(),
+                vec![],
+                0,
+                prepaid_gas,
+                GasWeight(0),
+                true,
+            )
+            .unwrap();
+    }
+    append_weighted_call(&mut manager, receipt_index, 1);
+
+    assert_eq!(manager.preview_gas_distribution(90), vec![(0, 0, 0), (0, 1, 20), (0, 2, 30)]);
+    // The first action's share and 20 of the second one's are below their prepaid gas.
+    assert_eq!(manager.distribute_unused_gas(90), GasDistribution::Partial { undistributed: 40 });
+    // Every action is entitled to 30, the first one keeps its larger prepaid gas instead.
+    assert_eq!(function_call_gas(&manager, 0), vec![70, 30, 30]);
+}
+
+#[test]
+fn test_insert_action() {
+    let mut ext = MockedExternal::new();
+    let mut manager = ReceiptManager::default();
+    let receipt_index = create_receipt(&mut manager, &mut ext, "alice.near");
+    manager.append_action_transfer(receipt_index, 1).unwrap();
+    append_weighted_call(&mut manager, receipt_index, 1);
+
+    manager.insert_action(receipt_index, 0, Action::CreateAccount(CreateAccountAction {})).unwrap();
+    assert_eq!(manager.pending_gas_weights(), vec![(0, 2, 1)]);
+    assert_eq!(
+        manager.insert_action(receipt_index, 4, Action::CreateAccount(CreateAccountAction {})),
+        Err(HostError::InvalidActionIndex { action_index: 4 }.into())
+    );
+    assert_eq!(
+        manager.insert_action(1, 0, Action::CreateAccount(CreateAccountAction {})),
+        Err(HostError::InvalidReceiptIndex { receipt_index: 1 }.into())
+    );
+
+    assert_eq!(manager.distribute_unused_gas(100), GasDistribution::All);
+    let actions = &manager.action_receipts[0].1.actions;
+    assert!(matches!(actions[0], Action::CreateAccount(_)));
+    assert!(matches!(actions[1], Action::Transfer(_)));
+    assert_eq!(function_call_gas(&manager, 0), vec![100]);
+}
+
+#[test]
+fn test_reserve_and_fill_action_slot() {
+    let mut ext = MockedExternal::new();
+    let mut manager = ReceiptManager::default();
+    let receipt_index = create_receipt(&mut manager, &mut ext, "alice.near");
+    append_weighted_call(&mut manager, receipt_index, 1);
+    let slot = manager.reserve_action_slot(receipt_index).unwrap();
+    manager.append_action_transfer(receipt_index, 1).unwrap();
+    assert_eq!(slot, 1);
+    assert_eq!(
+        manager.reserve_action_slot(1),
+        Err(HostError::InvalidReceiptIndex { receipt_index: 1 }.into())
+    );
+    assert_eq!(
+        manager.validate_all(),
+        Err(HostError::InvalidActionIndex { action_index: 1 }.into())
+    );
+
+    // The slot follows its action when actions are inserted before it.
+    manager.insert_action(receipt_index, 0, Action::CreateAccount(CreateAccountAction {})).unwrap();
+    assert_eq!(
+        manager.fill_action_slot(
+            receipt_index,
+            slot,
+            Action::Transfer(TransferAction { deposit: 2 })
+        ),
+        Err(HostError::InvalidActionIndex { action_index: 1 }.into())
+    );
+    manager
+        .fill_action_slot(receipt_index, 2, Action::Transfer(TransferAction { deposit: 2 }))
+        .unwrap();
+    assert_eq!(manager.validate_all(), Ok(()));
+    assert!(matches!(
+        manager.action_receipts[0].1.actions[2],
+        Action::Transfer(TransferAction { deposit: 2 })
+    ));
+}
+
+#[test]
+fn test_take_receipts_strips_unfilled_action_slots() {
+    let mut ext = MockedExternal::new();
+    let mut manager = ReceiptManager::default();
+    let first = create_receipt(&mut manager, &mut ext, "alice.near");
+    let second = create_receipt(&mut manager, &mut ext, "bob.near");
+    manager.reserve_action_slot(first).unwrap();
+    manager.append_action_transfer(first, 1).unwrap();
+    manager.reserve_action_slot(first).unwrap();
+    let slot = manager.reserve_action_slot(second).unwrap();
+
+    let taken = manager.take_receipts_for(&"alice.near".parse().unwrap());
+    assert!(matches!(taken[0].1.actions[..], [Action::Transfer(TransferAction { deposit: 1 })]));
+    // The slot of the remaining receipt can still be filled.
+    manager.fill_action_slot(0, slot, Action::Transfer(TransferAction { deposit: 2 })).unwrap();
+
+    manager.reserve_action_slot(0).unwrap();
+    let taken = manager.take_receipts();
+    assert!(matches!(taken[0].1.actions[..], [Action::Transfer(TransferAction { deposit: 2 })]));
+    assert_eq!(manager.validate_all(), Ok(()));
+}
+
+#[test]
+fn test_fill_action_slot_twice() {
+    let mut ext = MockedExternal::new();
+    let mut manager = ReceiptManager::default();
+    manager.set_deposit_limit(10);
+    let receipt_index = create_receipt(&mut manager, &mut ext, "alice.near");
+    let slot = manager.reserve_action_slot(receipt_index).unwrap();
+
+    assert_eq!(
+        manager.fill_action_slot(
+            receipt_index,
+            slot,
+            Action::Transfer(TransferAction { deposit: 11 })
+        ),
+        Err(HostError::BalanceExceeded.into())
+    );
+    manager
+        .fill_action_slot(receipt_index, slot, Action::Transfer(TransferAction { deposit: 5 }))
+        .unwrap();
+    assert_eq!(
+        manager.fill_action_slot(
+            receipt_index,
+            slot,
+            Action::Transfer(TransferAction { deposit: 1 })
+        ),
+        Err(HostError::InvalidActionIndex { action_index: 0 }.into())
+    );
+    assert!(matches!(
+        manager.action_receipts[0].1.actions[..],
+        [Action::Transfer(TransferAction { deposit: 5 })]
+    ));
+}
+
+#[test]
+fn test_unfilled_action_slots_are_hidden() {
+    let mut ext = MockedExternal::new();
+    let mut manager = ReceiptManager::default();
+    let costs = Arc::new(Mutex::new(vec![]));
+    let costs_clone = Arc::clone(&costs);
+    manager.set_construction_gas_hook(
+        RuntimeFeesConfig::test(),
+        Arc::new(move |gas| costs_clone.lock().unwrap().push(gas)),
+    );
+    let ready = Arc::new(Mutex::new(vec![]));
+    let ready_clone = ready.clone();
+    manager.set_on_receipt_ready(
+        1,
+        Arc::new(move |receipt_index| ready_clone.lock().unwrap().push(receipt_index)),
+    );
+    let receipt_index = create_receipt(&mut manager, &mut ext, "alice.near");
+    let first = manager.reserve_action_slot(receipt_index).unwrap();
+    let second = manager.reserve_action_slot(receipt_index).unwrap();
+
+    // Only the receipt creation was reported.
+    assert_eq!(costs.lock().unwrap().len(), 1);
+    assert!(ready.lock().unwrap().is_empty());
+    assert_eq!(manager.get_action(receipt_index, first), None);
+    assert_eq!(manager.duplicate_actions(receipt_index), Some(vec![]));
+    assert!(!manager.describe().contains("CreateAccount"));
+
+    manager
+        .fill_action_slot(receipt_index, second, Action::Transfer(TransferAction { deposit: 1 }))
+        .unwrap();
+    assert_eq!(costs.lock().unwrap().len(), 2);
+    assert_eq!(*ready.lock().unwrap(), vec![receipt_index]);
+    assert_eq!(
+        manager.get_action(receipt_index, second),
+        Some(&Action::Transfer(TransferAction { deposit: 1 }))
+    );
+    assert_eq!(manager.get_action(receipt_index, first), None);
+    assert_eq!(
+        manager.describe(),
+        "receipt 0 to alice.near: 0 input data dependencies, 0 output data receivers\n  \
+         Transfer deposit=1\n"
+    );
+}
+
+#[test]
+fn test_fill_action_slot_checks_action() {
+    let mut ext = MockedExternal::new();
+    let mut manager = ReceiptManager::default();
+    manager.set_max_contract_size(10);
+    manager.set_reject_zero_stake(true);
+    manager.set_reject_duplicate_key_nonces(true);
+    manager.set_reject_duplicate_method_names(true);
+    let receipt_index = create_receipt(&mut manager, &mut ext, "alice.near");
+    let public_key = PublicKey::empty(KeyType::ED25519);
+    manager
+        .append_action_add_key_with_full_access(receipt_index, public_key.try_to_vec().unwrap(), 7)
+        .unwrap();
+    let slot = manager.reserve_action_slot(receipt_index).unwrap();
+    let function_call_key = |allowance, method_names: &[&str]| {
+        Action::AddKey(AddKeyAction {
+            public_key: public_key.clone(),
+            access_key: AccessKey {
+                nonce: 8,
+                permission: AccessKeyPermission::FunctionCall(FunctionCallPermission {
+                    allowance,
+                    receiver_id: "bob.near".to_string(),
+                    method_names: method_names.iter().map(|name| name.to_string()).collect(),
+                }),
+            },
+        })
+    };
+
+    let cases = vec![
+        (
+            Action::DeployContract(DeployContractAction { code: vec![0; 11] }),
+            HostError::ContractSizeExceeded { size: 11, limit: 10 },
+        ),
+        (
+            Action::Stake(StakeAction { stake: 0, public_key: public_key.clone() }),
+            HostError::ZeroStake,
+        ),
+        (
+            Action::AddKey(AddKeyAction {
+                public_key: public_key.clone(),
+                access_key: AccessKey { nonce: 7, permission: AccessKeyPermission::FullAccess },
+            }),
+            HostError::DuplicateAccessKeyNonce { nonce: 7 },
+        ),
+        (function_call_key(Some(0), &[]), HostError::ZeroAllowance),
+        (
+            function_call_key(None, &["a", "a"]),
+            HostError::DuplicateMethodName { method_name: "a".to_string() },
+        ),
+    ];
+    for (action, error) in cases {
+        assert_eq!(manager.fill_action_slot(receipt_index, slot, action), Err(error.into()));
+    }
+    manager.fill_action_slot(receipt_index, slot, function_call_key(None, &["a"])).unwrap();
+    assert_eq!(manager.validate_all(), Ok(()));
+}
+
+#[test]
+fn test_delete_account_rejects_system_beneficiary() {
+    let mut ext = MockedExternal::new();
+    let mut manager = ReceiptManager::default();
+    let receipt_index = create_receipt(&mut manager, &mut ext, "alice.near");
+
+    assert_eq!(
+        manager.append_action_delete_account(receipt_index, "system".parse().unwrap()),
+        Err(HostError::InvalidAccountId.into())
+    );
+    assert!(manager.action_receipts[0].1.actions.is_empty());
+    manager.append_action_delete_account(receipt_index, "bob.near".parse().unwrap()).unwrap();
+
+    // Old protocol versions accept the system account, which `validate_all` still flags.
+    manager.set_allow_system_beneficiary(true);
+    manager.append_action_delete_account(receipt_index, "system".parse().unwrap()).unwrap();
+    assert_eq!(manager.validate_all(), Ok(()));
+    manager.set_allow_system_beneficiary(false);
+    assert_eq!(manager.validate_all(), Err(HostError::InvalidAccountId.into()));
+}
+
+#[test]
+fn test_has_distributable_weights() {
+    let mut ext = MockedExternal::new();
+    let mut manager = ReceiptManager::default();
+    let receipt_index = create_receipt(&mut manager, &mut ext, "alice.near");
+    append_weighted_call(&mut manager, receipt_index, 0);
+    assert!(!manager.has_distributable_weights());
+    assert_eq!(manager.distribute_unused_gas(100), GasDistribution::NoRatios);
+
+    append_weighted_call(&mut manager, receipt_index, 1);
+    assert!(manager.has_distributable_weights());
+    assert_eq!(manager.distribute_unused_gas(100), GasDistribution::All);
+    assert!(!manager.has_distributable_weights());
+}
+
+#[test]
+fn test_distribute_unused_gas_subset() {
+    let mut ext = MockedExternal::new();
+    let mut manager = ReceiptManager::default();
+    let first = create_receipt(&mut manager, &mut ext, "alice.near");
+    let second = create_receipt(&mut manager, &mut ext, "bob.near");
+    append_weighted_call(&mut manager, first, 1);
+    append_weighted_call(&mut manager, second, 1);
+    append_weighted_call(&mut manager, second, 3);
+
+    assert_eq!(manager.distribute_unused_gas_subset(100, second), GasDistribution::All);
+    assert_eq!(function_call_gas(&manager, 0), vec![0]);
+    assert_eq!(function_call_gas(&manager, 1), vec![25, 75]);
+    assert_eq!(manager.pending_gas_weights(), vec![(0, 0, 1)]);
+    assert_eq!(manager.distribute_unused_gas_subset(100, second), GasDistribution::NoRatios);
+}
+
+#[test]
+fn test_preview_gas_distribution() {
+    let mut ext = MockedExternal::new();
+    let mut manager = ReceiptManager::default();
+    let first = create_receipt(&mut manager, &mut ext, "alice.near");
+    let second = create_receipt(&mut manager, &mut ext, "bob.near");
+    append_weighted_call(&mut manager, first, 1);
+    manager.append_action_transfer(second, 1).unwrap();
+    append_weighted_call(&mut manager, second, 2);
+
+    let preview = manager.preview_gas_distribution(100);
+    assert_eq!(preview, vec![(0, 0, 33), (1, 1, 67)]);
+    assert_eq!(manager.preview_gas_distribution(100), preview);
+
+    assert_eq!(manager.distribute_unused_gas(100), GasDistribution::All);
+    for (receipt_index, _, gas) in preview {
+        assert_eq!(function_call_gas(&manager, receipt_index as usize), vec![gas]);
+    }
+}
+
+#[test]
+fn test_distribute_max_gas() {
+    assert_eq!(distribute(&[1], Gas::MAX), vec![Gas::MAX]);
+    assert_eq!(distribute(&[1, 1], Gas::MAX), vec![Gas::MAX / 2, Gas::MAX / 2 + 1]);
+    assert_eq!(distribute(&[u64::MAX, 1], Gas::MAX), vec![Gas::MAX - 1, 1]);
+
+    let mut ext = MockedExternal::new();
+    let mut manager = ReceiptManager::default();
+    let receipt_index = create_receipt(&mut manager, &mut ext, "alice.near");
+    append_weighted_call(&mut manager, receipt_index, 1);
+    assert_eq!(manager.distribute_unused_gas(Gas::MAX), GasDistribution::All);
+    assert_eq!(function_call_gas(&manager, 0), vec![Gas::MAX]);
+}
+
+#[test]
+fn test_distribute_sums_to_gas() {
+    // Xorshift generator, so that the checked inputs are the same on every run.
+    let mut state = 0x2545_f491_4f6c_dd1d_u64;
+    let mut next = || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state
+    };
+    for _ in 0..10_000 {
+        let num_weights = (next() % 8) as usize;
+        let weights = (0..num_weights)
+            .map(|_| match next() % 4 {
+                0 => 0,
+                1 => u64::MAX,
+                2 => next() % 10,
+                _ => next(),
+            })
+            .collect::<Vec<_>>();
+        let gas = match next() % 3 {
+            0 => Gas::MAX,
+            1 => next() % 1000,
+            _ => next(),
+        };
+
+        let shares = distribute(&weights, gas);
+        assert_eq!(shares.len(), weights.len());
+        let total: u128 = shares.iter().map(|share| *share as u128).sum();
+        if weights.iter().any(|weight| *weight > 0) {
+            assert_eq!(total, gas as u128, "weights: {:?}, gas: {}", weights, gas);
+        } else {
+            assert_eq!(total, 0, "weights: {:?}, gas: {}", weights, gas);
+        }
+        // Only the last share receives the rounding remainder.
+        for (weight, share) in weights.iter().zip(&shares).take(weights.len().saturating_sub(1)) {
+            if *weight == 0 {
+                assert_eq!(*share, 0, "weights: {:?}, gas: {}", weights, gas);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_empty_receipt_indices() {
+    let mut ext = MockedExternal::new();
+    let mut manager = ReceiptManager::default();
+    assert!(manager.empty_receipt_indices().is_empty());
+    let empty = create_receipt(&mut manager, &mut ext, "alice.near");
+    let populated = create_receipt(&mut manager, &mut ext, "bob.near");
+    manager.append_action_transfer(populated, 1).unwrap();
+
+    assert_eq!(manager.empty_receipt_indices(), vec![empty]);
+}
+
+#[test]
+fn test_take_receipts_with_diagnostics() {
+    let mut ext = MockedExternal::new();
+    let mut manager = ReceiptManager::default();
+    for receiver_id in ["alice.near", "contract.near", "bob.near", "contract.near"] {
+        let receipt_index = create_receipt(&mut manager, &mut ext, receiver_id);
+        manager.append_action_transfer(receipt_index, 1).unwrap();
+    }
+
+    let predecessor_id: AccountId = "contract.near".parse().unwrap();
+    let signer_id: AccountId = "signer.near".parse().unwrap();
+    let signer_public_key = PublicKey::empty(KeyType::ED25519);
+    let (taken, diagnostics) =
+        manager.take_receipts_with_diagnostics(&predecessor_id, &signer_id, &signer_public_key, 7);
+    assert_eq!(taken.len(), 4);
+    assert_eq!(diagnostics, TakeReceiptsDiagnostics { reflexive_receipts: vec![1, 3] });
+}
+
+#[test]
+fn test_take_receipts_simple() {
+    let mut ext = MockedExternal::new();
+    let mut manager = ReceiptManager::with_gas_price(7);
+    for receiver_id in ["alice.near", "bob.near"] {
+        let receipt_index = create_receipt(&mut manager, &mut ext, receiver_id);
+        manager.append_action_transfer(receipt_index, 1).unwrap();
+    }
+
+    let predecessor_id: AccountId = "predecessor.near".parse().unwrap();
+    let signer_id: AccountId = "signer.near".parse().unwrap();
+    let signer_public_key = PublicKey::empty(KeyType::ED25519);
+    let taken = manager.take_receipts_simple(&predecessor_id, &signer_id, &signer_public_key);
+    assert_eq!(taken.len(), 2);
+    for receipt in taken {
+        match receipt.receipt {
+            ReceiptEnum::Action(action_receipt) => assert_eq!(action_receipt.gas_price, 7),
+            ReceiptEnum::Data(_) => panic!("expected an action receipt"),
+        }
+    }
+}
+
+#[test]
+fn test_invalid_public_key_reason() {
+    let mut ext = MockedExternal::new();
+    let mut manager = ReceiptManager::default();
+    let receipt_index = create_receipt(&mut manager, &mut ext, "alice.near");
+
+    match manager.append_action_delete_key(receipt_index, vec![0]) {
+        Err(VMLogicError::HostError(HostError::InvalidPublicKeyWithReason { msg })) => {
+            assert!(!msg.is_empty())
+        }
+        result => panic!("unexpected result: {:?}", result),
+    }
+}
+
+#[test]
+fn test_invalid_method_name_utf8_offset() {
+    let mut ext = MockedExternal::new();
+    let mut manager = ReceiptManager::default();
+    let receipt_index = create_receipt(&mut manager, &mut ext, "alice.near");
+    let invalid_utf8 = b"meth\xffod".to_vec();
+    let error = Err(HostError::InvalidMethodNameUtf8 { valid_up_to: 4 }.into());
+
+    assert_eq!(
+        manager.append_action_function_call_weight(
+            receipt_index,
+            invalid_utf8.clone(),
+            vec![],
+            0,
+            0,
+            GasWeight(0),
+        ),
+        error
+    );
+    assert_eq!(
+        manager.append_action_add_key_with_function_call(
+            receipt_index,
+            PublicKey::empty(KeyType::ED25519).try_to_vec().unwrap(),
+            0,
+            None,
+            "bob.near".parse().unwrap(),
+            vec![b"method".to_vec(), invalid_utf8],
+        ),
+        error
+    );
+    assert!(manager.action_receipts[0].1.actions.is_empty());
+}
+
+#[test]
+fn test_total_action_count() {
+    let mut ext = MockedExternal::new();
+    let mut manager = ReceiptManager::default();
+    let first = create_receipt(&mut manager, &mut ext, "alice.near");
+    let second = create_receipt(&mut manager, &mut ext, "bob.near");
+    assert_eq!(manager.total_action_count(), 0);
+    manager.append_action_transfer(first, 1).unwrap();
+    manager.append_action_transfer(first, 2).unwrap();
+    append_weighted_call(&mut manager, second, 1);
+
+    assert_eq!(manager.total_action_count(), 3);
+}
+
+#[test]
+fn test_max_total_actions() {
+    let mut ext = MockedExternal::new();
+    let mut manager = ReceiptManager::default();
+    manager.set_max_total_actions(3);
+    let first = create_receipt(&mut manager, &mut ext, "alice.near");
+    let second = create_receipt(&mut manager, &mut ext, "bob.near");
+    manager.append_action_transfer(first, 1).unwrap();
+    manager.append_action_create_account(second).unwrap();
+    append_weighted_call(&mut manager, second, 1);
+
+    let error = Err(HostError::NumberActionsExceeded { limit: 3 }.into());
+    assert_eq!(manager.append_action_transfer(first, 1), error);
+    assert_eq!(
+        manager.insert_action(first, 0, Action::CreateAccount(CreateAccountAction {})),
+        error
+    );
+    assert_eq!(manager.total_action_count(), 3);
+    assert_eq!(manager.validate_all(), Ok(()));
+
+    manager.set_max_total_actions(2);
+    assert_eq!(manager.validate_all(), Err(HostError::NumberActionsExceeded { limit: 2 }.into()));
+}
+
+#[test]
+fn test_split_receipt() {
+    let mut ext = MockedExternal::new();
+    let mut manager = ReceiptManager::default();
+    let receipt_index = create_receipt(&mut manager, &mut ext, "alice.near");
+    manager.append_action_transfer(receipt_index, 1).unwrap();
+    append_weighted_call(&mut manager, receipt_index, 1);
+    manager.append_action_transfer(receipt_index, 2).unwrap();
+    append_weighted_call(&mut manager, receipt_index, 2);
+
+    assert_eq!(
+        manager.split_receipt(receipt_index, 5),
+        Err(HostError::InvalidActionIndex { action_index: 5 }.into())
+    );
+    assert_eq!(
+        manager.split_receipt(1, 0),
+        Err(HostError::InvalidReceiptIndex { receipt_index: 1 }.into())
+    );
+    let new_index = manager.split_receipt(receipt_index, 2).unwrap();
+    assert_eq!(new_index, 1);
+    assert_eq!(manager.get_receipt_receiver(new_index).as_ref(), "alice.near");
+    assert_eq!(manager.action_receipts[0].1.actions.len(), 2);
+    assert!(matches!(
+        manager.action_receipts[1].1.actions[..],
+        [Action::Transfer(TransferAction { deposit: 2 }), Action::FunctionCall(_)]
+    ));
+    assert_eq!(manager.pending_gas_weights(), vec![(0, 1, 1), (1, 1, 2)]);
+
+    assert_eq!(manager.distribute_unused_gas(30), GasDistribution::All);
+    assert_eq!(function_call_gas(&manager, 0), vec![10]);
+    assert_eq!(function_call_gas(&manager, 1), vec![20]);
+}
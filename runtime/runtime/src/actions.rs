@@ -254,7 +254,7 @@ pub(crate) fn action_function_call(
                 receipt: ReceiptEnum::Action(ActionReceipt {
                     signer_id: action_receipt.signer_id.clone(),
                     signer_public_key: action_receipt.signer_public_key.clone(),
-                    gas_price: action_receipt.gas_price,
+                    gas_price: receipt.gas_price.unwrap_or(action_receipt.gas_price),
                     output_data_receivers: receipt.output_data_receivers,
                     input_data_ids: receipt.input_data_ids,
                     actions: receipt.actions,
@@ -0,0 +1,96 @@
+#[macro_use]
+extern crate bencher;
+
+use bencher::{black_box, Bencher};
+use near_chain::{ChainGenesis, ChainStore, ChainStoreAccess, Provenance};
+use near_chain_configs::Genesis;
+use near_client::test_utils::TestEnv;
+use near_crypto::{InMemorySigner, KeyType};
+use near_primitives::block::Block;
+use near_primitives::runtime::config_store::RuntimeConfigStore;
+use near_primitives::transaction::{Action, SignedTransaction, TransferAction};
+use near_primitives::types::NumShards;
+use near_primitives::utils::get_num_seats_per_shard;
+use near_store::test_utils::create_test_store;
+use nearcore::config::GenesisExt;
+use nearcore::{NightshadeRuntime, TrackedConfig};
+use state_viewer::tx_dump::BlockChunks;
+use std::path::Path;
+use std::sync::Arc;
+
+const NUM_SHARDS: NumShards = 4;
+/// Transactions signed by the account of each shard.
+const TXS_PER_SHARD: u64 = 100;
+
+/// Produces blocks with `TXS_PER_SHARD` transfers from the account of every shard and returns
+/// the environment together with the block including the most transactions.
+fn setup() -> (TestEnv, Block) {
+    let genesis = Genesis::test_sharded(
+        (0..NUM_SHARDS).map(|i| format!("test{}", i).parse().unwrap()).collect(),
+        1,
+        get_num_seats_per_shard(NUM_SHARDS, 1),
+    );
+    let runtime = NightshadeRuntime::test_with_runtime_config_store(
+        Path::new("."),
+        create_test_store(),
+        &genesis,
+        TrackedConfig::AllShards,
+        RuntimeConfigStore::test(),
+    );
+    let mut env =
+        TestEnv::builder(ChainGenesis::test()).runtime_adapters(vec![Arc::new(runtime)]).build();
+    let genesis_hash = *env.clients[0].chain.genesis().hash();
+
+    for shard in 0..NUM_SHARDS {
+        let signer_id = format!("test{}", shard);
+        let signer =
+            InMemorySigner::from_seed(signer_id.parse().unwrap(), KeyType::ED25519, &signer_id);
+        for nonce in 1..=TXS_PER_SHARD {
+            let tx = SignedTransaction::from_actions(
+                nonce,
+                signer.account_id.clone(),
+                format!("test{}", (shard + 1) % NUM_SHARDS).parse().unwrap(),
+                &signer,
+                vec![Action::Transfer(TransferAction { deposit: 1 })],
+                genesis_hash,
+            );
+            env.clients[0].process_tx(tx, false, false);
+        }
+    }
+    let mut busiest: Option<(u64, Block)> = None;
+    for height in 1..=5 {
+        let block = env.clients[0].produce_block(height).unwrap().unwrap();
+        env.process_block(0, block.clone(), Provenance::PRODUCED);
+        let tx_count = BlockChunks::new(env.clients[0].chain.store(), &block).tx_count();
+        if busiest.as_ref().map_or(true, |(count, _)| tx_count > *count) {
+            busiest = Some((tx_count, block));
+        }
+    }
+    (env, busiest.unwrap().1)
+}
+
+/// Each iteration reads through a fresh `ChainStore`, so that chunks are decoded every time
+/// instead of being served from the caches.
+fn fresh_chain_store(env: &TestEnv) -> ChainStore {
+    let chain_store = env.clients[0].chain.store();
+    ChainStore::new(chain_store.store().clone(), chain_store.get_genesis_height(), false)
+}
+
+fn fetch_chunks_sequential(bench: &mut Bencher) {
+    let (env, block) = setup();
+    bench.iter(|| {
+        let chain_store = fresh_chain_store(&env);
+        black_box(BlockChunks::new_sequential(&chain_store, &block).tx_count())
+    });
+}
+
+fn fetch_chunks_parallel(bench: &mut Bencher) {
+    let (env, block) = setup();
+    bench.iter(|| {
+        let chain_store = fresh_chain_store(&env);
+        black_box(BlockChunks::new(&chain_store, &block).tx_count())
+    });
+}
+
+benchmark_group!(benches, fetch_chunks_sequential, fetch_chunks_parallel);
+benchmark_main!(benches);
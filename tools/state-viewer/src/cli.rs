@@ -64,6 +64,9 @@ pub enum StateViewerSubCommand {
     /// even if it's not included in any block on disk
     #[clap(alias = "apply_receipt")]
     ApplyReceipt(ApplyReceiptCmd),
+    /// Print the transactions included in the block at the given height.
+    #[clap(alias = "dump_tx")]
+    DumpTx(DumpTxCmd),
 }
 
 impl StateViewerSubCommand {
@@ -94,6 +97,7 @@ impl StateViewerSubCommand {
             StateViewerSubCommand::ApplyChunk(cmd) => cmd.run(home_dir, near_config, store),
             StateViewerSubCommand::ApplyTx(cmd) => cmd.run(home_dir, near_config, store),
             StateViewerSubCommand::ApplyReceipt(cmd) => cmd.run(home_dir, near_config, store),
+            StateViewerSubCommand::DumpTx(cmd) => cmd.run(near_config, store),
         }
     }
 }
@@ -403,3 +407,15 @@ impl ApplyReceiptCmd {
         apply_receipt(home_dir, near_config, store, hash).unwrap();
     }
 }
+
+#[derive(Parser)]
+pub struct DumpTxCmd {
+    #[clap(long)]
+    height: BlockHeight,
+}
+
+impl DumpTxCmd {
+    pub fn run(self, near_config: NearConfig, store: Store) {
+        dump_tx(self.height, near_config, store)
+    }
+}
@@ -1,6 +1,7 @@
 use crate::apply_chain_range::apply_chain_range;
 use crate::state_dump::state_dump;
 use crate::state_dump::state_dump_redis;
+use crate::tx_dump::{print_tx_dump, BlockChunks};
 use crate::{apply_chunk, epoch_info};
 use ansi_term::Color::Red;
 use near_chain::chain::collect_receipts_from_response;
@@ -625,6 +626,18 @@ pub(crate) fn get_chunk(chunk_hash: ChunkHash, near_config: NearConfig, store: S
     println!("Chunk: {:#?}", chunk);
 }
 
+pub(crate) fn dump_tx(height: BlockHeight, near_config: NearConfig, store: Store) {
+    let chain_store = ChainStore::new(
+        store,
+        near_config.genesis.config.genesis_height,
+        !near_config.client_config.archive,
+    );
+    let block_hash = chain_store.get_block_hash_by_height(height).unwrap();
+    let block = chain_store.get_block(&block_hash).unwrap();
+    let block_chunks = BlockChunks::new(&chain_store, &block);
+    print_tx_dump(block_chunks.transactions(), std::io::stdout()).unwrap();
+}
+
 pub(crate) fn get_partial_chunk(
     partial_chunk_hash: ChunkHash,
     near_config: NearConfig,
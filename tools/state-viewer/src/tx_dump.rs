@@ -0,0 +1,1612 @@
+use near_chain::{ChainStore, ChainStoreAccess};
+use near_crypto::PublicKey;
+use near_primitives::block::Block;
+use near_primitives::hash::{hash, CryptoHash};
+use near_primitives::receipt::Receipt;
+use near_primitives::sharding::{ChunkHash, ShardChunk};
+use near_primitives::transaction::{
+    Action, AddKeyAction, DeleteKeyAction, DeployContractAction, ExecutionOutcome,
+    FunctionCallAction, SignedTransaction, StakeAction, TransferAction,
+};
+use near_primitives::types::{
+    AccountId, Balance, BlockHeight, Gas, Nonce, ShardId, TimestampNanos,
+};
+use near_primitives::views::SignedTransactionView;
+use near_store::DBCol;
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use serde::Serialize;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::io::{self, Write};
+use std::sync::Arc;
+use tracing::warn;
+
+/// The chunks included in a block, read from the store once.
+///
+/// Dump functions analysing the same block accept a `BlockChunks` so that the chunks are not
+/// refetched for every analysis.
+pub struct BlockChunks {
+    /// Chunks included in the block, in the order of shard ids.
+    chunks: Vec<Arc<ShardChunk>>,
+}
+
+impl BlockChunks {
+    /// Reads the chunks included in `block`.
+    ///
+    /// Chunks are fetched and decoded in parallel. The caches of `ChainStore` are not
+    /// thread-safe, so each worker reads through its own `ChainStore` on top of the shared
+    /// `Store`. Chunks which are missing from the store are skipped.
+    pub fn new(chain_store: &ChainStore, block: &Block) -> Self {
+        let store = chain_store.store();
+        let genesis_height = chain_store.get_genesis_height();
+        let height = block.header().height();
+        Self::with_fetcher(block, |chunk_hash| {
+            let chain_store = ChainStore::new(store.clone(), genesis_height, false);
+            get_chunk(&chain_store, chunk_hash, height)
+        })
+    }
+
+    /// Same as [`BlockChunks::new`], but fetches the chunks one after another through
+    /// `chain_store`, whose caches are reused.
+    pub fn new_sequential(chain_store: &ChainStore, block: &Block) -> Self {
+        let height = block.header().height();
+        let chunks = included_chunk_hashes(block)
+            .iter()
+            .filter_map(|chunk_hash| get_chunk(chain_store, chunk_hash, height))
+            .collect();
+        Self { chunks }
+    }
+
+    /// Reads the chunks included in `block` with `fetch_chunk`, which is called once per chunk.
+    fn with_fetcher(
+        block: &Block,
+        fetch_chunk: impl Fn(&ChunkHash) -> Option<Arc<ShardChunk>> + Sync,
+    ) -> Self {
+        let chunks = included_chunk_hashes(block)
+            .into_par_iter()
+            .filter_map(|chunk_hash| fetch_chunk(&chunk_hash))
+            .collect();
+        Self { chunks }
+    }
+
+    /// Returns the transactions of each chunk together with its shard id.
+    pub fn transactions_by_shard(
+        &self,
+    ) -> impl Iterator<Item = (ShardId, &[SignedTransaction])> + '_ {
+        self.chunks.iter().map(|chunk| (chunk.shard_id(), chunk.transactions().as_slice()))
+    }
+
+    /// Returns the transactions of all chunks, ordered by shard id and then by their position
+    /// within the chunk.
+    ///
+    /// The transactions are borrowed from the chunks, so unlike [`tx_dump`] this doesn't clone
+    /// them. Prefer it for read-only analysis of large blocks.
+    pub fn transactions(&self) -> impl Iterator<Item = &SignedTransaction> + '_ {
+        self.transactions_by_shard().flat_map(|(_, txs)| txs)
+    }
+
+    /// Returns the number of transactions in all chunks.
+    pub fn tx_count(&self) -> u64 {
+        self.chunks.iter().map(|chunk| chunk.transactions().len() as u64).sum()
+    }
+
+    /// Returns the receipts of all chunks, in the order of shard ids.
+    fn receipts(&self) -> impl Iterator<Item = &Receipt> + '_ {
+        self.chunks.iter().flat_map(|chunk| chunk.receipts())
+    }
+}
+
+/// Returns the hashes of the chunks included in `block`, in the order of shard ids.
+fn included_chunk_hashes(block: &Block) -> Vec<ChunkHash> {
+    let height = block.header().height();
+    block
+        .chunks()
+        .iter()
+        .filter(|chunk_header| chunk_header.height_included() == height)
+        .map(|chunk_header| chunk_header.chunk_hash())
+        .collect()
+}
+
+/// Reads a chunk of the block at `height`, logging a warning if it is missing from the store.
+fn get_chunk(
+    chain_store: &ChainStore,
+    chunk_hash: &ChunkHash,
+    height: BlockHeight,
+) -> Option<Arc<ShardChunk>> {
+    match chain_store.get_chunk(chunk_hash) {
+        Ok(chunk) => Some(chunk),
+        Err(_) => {
+            warn!(
+                target: "state-viewer",
+                "chunk {:?} of block at height {} is not saved", chunk_hash, height
+            );
+            None
+        }
+    }
+}
+
+/// Same as [`get_chunk`], but reads the chunk straight from the store, bypassing the chunk
+/// cache of `chain_store`, so that the chunk is freed once the caller drops it.
+fn get_chunk_uncached(
+    chain_store: &ChainStore,
+    chunk_hash: &ChunkHash,
+    height: BlockHeight,
+) -> Option<ShardChunk> {
+    match chain_store.store().get_ser(DBCol::Chunks, chunk_hash.as_ref()) {
+        Ok(Some(chunk)) => Some(chunk),
+        _ => {
+            warn!(
+                target: "state-viewer",
+                "chunk {:?} of block at height {} is not saved", chunk_hash, height
+            );
+            None
+        }
+    }
+}
+
+/// Returns the transactions of the chunks included in `block`, ordered by shard id and then
+/// by their position within the chunk.
+///
+/// The order doesn't depend on the order in which chunks are fetched, so the result is the
+/// same across runs. Use [`print_tx_dump`] to print it.
+///
+/// Every transaction is cloned together with its actions, so the block's transactions end
+/// up allocated twice: one clone per transaction and per action, including the arguments of
+/// function calls and the code of deployments. Callers that only read them should iterate
+/// [`BlockChunks::transactions`] instead, which borrows them and allocates nothing per
+/// transaction.
+pub fn tx_dump(chain_store: &ChainStore, block: &Block) -> Vec<SignedTransaction> {
+    BlockChunks::new(chain_store, block).transactions().cloned().collect()
+}
+
+/// Returns the number of transactions in the chunks included in `block`, without cloning them.
+pub fn tx_count(chain_store: &ChainStore, block: &Block) -> u64 {
+    BlockChunks::new(chain_store, block).tx_count()
+}
+
+/// Returns the height of the block with the most transactions among the blocks at heights
+/// `start_height..=end_height`, along with its number of transactions. Ties go to the lowest
+/// height and heights without a block are skipped.
+///
+/// Returns `None` if there are no blocks in the range.
+pub fn find_busiest_block(
+    chain_store: &ChainStore,
+    start_height: BlockHeight,
+    end_height: BlockHeight,
+) -> Option<(BlockHeight, u64)> {
+    let mut busiest: Option<(BlockHeight, u64)> = None;
+    for height in start_height..=end_height {
+        let block = match chain_store
+            .get_block_hash_by_height(height)
+            .and_then(|block_hash| chain_store.get_block(&block_hash))
+        {
+            Ok(block) => block,
+            Err(_) => continue,
+        };
+        let count = tx_count(chain_store, &block);
+        if busiest.map_or(true, |(_, max_count)| count > max_count) {
+            busiest = Some((height, count));
+        }
+    }
+    busiest
+}
+
+/// Returns the transactions of the chunks included in `block` like [`tx_dump`], each paired
+/// with its hash, which explorers use to index transactions.
+pub fn tx_dump_with_hashes(
+    chain_store: &ChainStore,
+    block: &Block,
+) -> Vec<(CryptoHash, SignedTransaction)> {
+    BlockChunks::new(chain_store, block)
+        .transactions()
+        .map(|tx| (tx.get_hash(), tx.clone()))
+        .collect()
+}
+
+/// Returns the transactions of the chunks included in `block` like [`tx_dump`], each joined
+/// with its execution outcome in `block`, or `None` if no outcome is recorded.
+pub fn tx_dump_with_outcomes(
+    chain_store: &ChainStore,
+    block: &Block,
+) -> Vec<(SignedTransaction, Option<ExecutionOutcome>)> {
+    let block_hash = block.hash();
+    BlockChunks::new(chain_store, block)
+        .transactions()
+        .map(|tx| {
+            let tx_hash = tx.get_hash();
+            let outcomes = chain_store.get_outcomes_by_id(&tx_hash).unwrap_or_else(|err| {
+                warn!(
+                    target: "state-viewer",
+                    "failed to read outcomes of transaction {}: {}", tx_hash, err
+                );
+                vec![]
+            });
+            let outcome = outcomes
+                .into_iter()
+                .find(|outcome| &outcome.block_hash == block_hash)
+                .map(|outcome| outcome.outcome_with_id.outcome);
+            (tx.clone(), outcome)
+        })
+        .collect()
+}
+
+/// Writes `txs` to `writer`, one line per transaction with its hash, signer, receiver, nonce
+/// and number of actions.
+pub fn print_tx_dump<'a>(
+    txs: impl IntoIterator<Item = &'a SignedTransaction>,
+    mut writer: impl Write,
+) -> io::Result<()> {
+    for tx in txs {
+        writeln!(
+            writer,
+            "{} {} -> {} nonce={} #actions={}",
+            tx.get_hash(),
+            tx.transaction.signer_id,
+            tx.transaction.receiver_id,
+            tx.transaction.nonce,
+            tx.transaction.actions.len()
+        )?;
+    }
+    Ok(())
+}
+
+/// Returns the transactions of the chunks included in `block` like [`tx_dump`], each paired
+/// with the height and the timestamp of the block.
+///
+/// Useful when dumps of several blocks are concatenated.
+pub fn tx_dump_annotated(
+    chain_store: &ChainStore,
+    block: &Block,
+) -> Vec<(BlockHeight, TimestampNanos, SignedTransaction)> {
+    let height = block.header().height();
+    let timestamp = block.header().raw_timestamp();
+    BlockChunks::new(chain_store, block)
+        .transactions()
+        .map(|tx| (height, timestamp, tx.clone()))
+        .collect()
+}
+
+/// Calls `callback` with every transaction of the chunks included in `block` and the shard id
+/// of its chunk, in the same order as [`tx_dump`].
+///
+/// Chunks are read one at a time straight from the store, bypassing the chunk cache of
+/// `chain_store`, and dropped once their transactions are processed, so the transactions of
+/// the whole block are never held in memory together.
+pub fn tx_dump_stream(
+    chain_store: &ChainStore,
+    block: &Block,
+    mut callback: impl FnMut(ShardId, &SignedTransaction),
+) {
+    let height = block.header().height();
+    for chunk_hash in included_chunk_hashes(block) {
+        if let Some(chunk) = get_chunk_uncached(chain_store, &chunk_hash, height) {
+            for tx in chunk.transactions() {
+                callback(chunk.shard_id(), tx);
+            }
+        }
+    }
+}
+
+/// Returns the transactions of the chunks included in `block` grouped by shard, in the order
+/// of shard ids.
+pub fn tx_dump_by_shard(
+    chain_store: &ChainStore,
+    block: &Block,
+) -> Vec<(ShardId, Vec<SignedTransaction>)> {
+    BlockChunks::new(chain_store, block)
+        .transactions_by_shard()
+        .map(|(shard_id, txs)| (shard_id, txs.to_vec()))
+        .collect()
+}
+
+/// Returns the receipts of the chunks included in `block`, ordered by shard id and then by
+/// their position within the chunk.
+pub fn receipt_dump(chain_store: &ChainStore, block: &Block) -> Vec<Receipt> {
+    BlockChunks::new(chain_store, block).receipts().cloned().collect()
+}
+
+/// Returns the transactions with at least one function call to `method_name`.
+pub fn tx_dump_by_method(chunks: &BlockChunks, method_name: &str) -> Vec<SignedTransaction> {
+    chunks
+        .transactions()
+        .filter(|tx| {
+            tx.transaction.actions.iter().any(|action| match action {
+                Action::FunctionCall(FunctionCallAction { method_name: name, .. }) => {
+                    name == method_name
+                }
+                _ => false,
+            })
+        })
+        .cloned()
+        .collect()
+}
+
+/// Returns the `n` transactions with the most gas attached to their function calls, in
+/// descending order of gas. Transactions with the same gas are ordered by their hash.
+pub fn tx_dump_top_by_gas(chunks: &BlockChunks, n: usize) -> Vec<SignedTransaction> {
+    let mut txs = chunks
+        .transactions()
+        .map(|tx| (attached_gas_and_deposit(tx).0, tx.get_hash(), tx))
+        .collect::<Vec<_>>();
+    txs.sort_by(|(gas_a, hash_a, _), (gas_b, hash_b, _)| {
+        gas_b.cmp(gas_a).then_with(|| hash_a.cmp(hash_b))
+    });
+    txs.into_iter().take(n).map(|(_, _, tx)| tx.clone()).collect()
+}
+
+/// Returns the number of transactions signed by each account.
+pub fn tx_dump_per_signer(chunks: &BlockChunks) -> HashMap<AccountId, u64> {
+    let mut counts = HashMap::new();
+    for tx in chunks.transactions() {
+        *counts.entry(tx.transaction.signer_id.clone()).or_default() += 1;
+    }
+    counts
+}
+
+/// Returns the nonces used by each access key, identified by the signer and its public key, in
+/// the order of the transactions.
+pub fn tx_dump_nonces(chunks: &BlockChunks) -> HashMap<(AccountId, PublicKey), Vec<Nonce>> {
+    let mut nonces: HashMap<(AccountId, PublicKey), Vec<Nonce>> = HashMap::new();
+    for tx in chunks.transactions() {
+        let tx = &tx.transaction;
+        nonces.entry((tx.signer_id.clone(), tx.public_key.clone())).or_default().push(tx.nonce);
+    }
+    nonces
+}
+
+/// Returns the transactions addressed to `receiver_id`.
+pub fn tx_dump_to_receiver(
+    chunks: &BlockChunks,
+    receiver_id: &AccountId,
+) -> Vec<SignedTransaction> {
+    chunks.transactions().filter(|tx| &tx.transaction.receiver_id == receiver_id).cloned().collect()
+}
+
+/// Returns the transactions without any actions, which are usually malformed or probing.
+pub fn tx_dump_empty_action_txs(chunks: &BlockChunks) -> Vec<SignedTransaction> {
+    chunks.transactions().filter(|tx| tx.transaction.actions.is_empty()).cloned().collect()
+}
+
+/// Returns the receivers of the transactions with at least one function call, i.e. the
+/// contracts called directly by the transactions of the block.
+pub fn tx_dump_called_contracts(chunks: &BlockChunks) -> HashSet<AccountId> {
+    chunks
+        .transactions()
+        .filter(|tx| {
+            tx.transaction.actions.iter().any(|action| matches!(action, Action::FunctionCall(_)))
+        })
+        .map(|tx| tx.transaction.receiver_id.clone())
+        .collect()
+}
+
+/// Returns the account and the hash of the deployed code of every deploy contract action, in
+/// the order of the transactions.
+pub fn tx_dump_deployments(chunks: &BlockChunks) -> Vec<(AccountId, CryptoHash)> {
+    chunks
+        .transactions()
+        .flat_map(|tx| {
+            tx.transaction.actions.iter().filter_map(move |action| match action {
+                Action::DeployContract(DeployContractAction { code }) => {
+                    Some((tx.transaction.receiver_id.clone(), hash(code)))
+                }
+                _ => None,
+            })
+        })
+        .collect()
+}
+
+/// Returns the sum of the amounts of the stake actions by staking account and validator key.
+/// Sums saturate instead of overflowing.
+pub fn tx_dump_stake_changes(chunks: &BlockChunks) -> HashMap<(AccountId, PublicKey), Balance> {
+    let mut stakes: HashMap<(AccountId, PublicKey), Balance> = HashMap::new();
+    for tx in chunks.transactions() {
+        for action in &tx.transaction.actions {
+            let StakeAction { stake, public_key } = match action {
+                Action::Stake(stake) => stake,
+                #[cfg(feature = "protocol_feature_chunk_only_producers")]
+                Action::StakeChunkOnly(stake) => stake,
+                _ => continue,
+            };
+            let total =
+                stakes.entry((tx.transaction.receiver_id.clone(), public_key.clone())).or_default();
+            *total = total.saturating_add(*stake);
+        }
+    }
+    stakes
+}
+
+/// Returns the transactions signed with `public_key` or adding or deleting an access key for it.
+pub fn tx_dump_by_public_key(
+    chunks: &BlockChunks,
+    public_key: &PublicKey,
+) -> Vec<SignedTransaction> {
+    chunks
+        .transactions()
+        .filter(|tx| {
+            &tx.transaction.public_key == public_key
+                || tx.transaction.actions.iter().any(|action| match action {
+                    Action::AddKey(AddKeyAction { public_key: key, .. })
+                    | Action::DeleteKey(DeleteKeyAction { public_key: key }) => key == public_key,
+                    _ => false,
+                })
+        })
+        .cloned()
+        .collect()
+}
+
+/// Kind of an [`Action`], without its arguments.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ActionKind {
+    CreateAccount,
+    DeployContract,
+    FunctionCall,
+    Transfer,
+    Stake,
+    AddKey,
+    DeleteKey,
+    DeleteAccount,
+    #[cfg(feature = "protocol_feature_chunk_only_producers")]
+    StakeChunkOnly,
+}
+
+impl From<&Action> for ActionKind {
+    fn from(action: &Action) -> Self {
+        match action {
+            Action::CreateAccount(_) => ActionKind::CreateAccount,
+            Action::DeployContract(_) => ActionKind::DeployContract,
+            Action::FunctionCall(_) => ActionKind::FunctionCall,
+            Action::Transfer(_) => ActionKind::Transfer,
+            Action::Stake(_) => ActionKind::Stake,
+            Action::AddKey(_) => ActionKind::AddKey,
+            Action::DeleteKey(_) => ActionKind::DeleteKey,
+            Action::DeleteAccount(_) => ActionKind::DeleteAccount,
+            #[cfg(feature = "protocol_feature_chunk_only_producers")]
+            Action::StakeChunkOnly(_) => ActionKind::StakeChunkOnly,
+        }
+    }
+}
+
+impl ActionKind {
+    /// Returns the name of the kind, which is also the name of the [`Action`] variant.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ActionKind::CreateAccount => "CreateAccount",
+            ActionKind::DeployContract => "DeployContract",
+            ActionKind::FunctionCall => "FunctionCall",
+            ActionKind::Transfer => "Transfer",
+            ActionKind::Stake => "Stake",
+            ActionKind::AddKey => "AddKey",
+            ActionKind::DeleteKey => "DeleteKey",
+            ActionKind::DeleteAccount => "DeleteAccount",
+            #[cfg(feature = "protocol_feature_chunk_only_producers")]
+            ActionKind::StakeChunkOnly => "StakeChunkOnly",
+        }
+    }
+}
+
+/// Returns the transactions with at least one action of the given kind.
+pub fn tx_dump_by_action_kind(chunks: &BlockChunks, kind: ActionKind) -> Vec<SignedTransaction> {
+    chunks
+        .transactions()
+        .filter(|tx| tx.transaction.actions.iter().any(|action| ActionKind::from(action) == kind))
+        .cloned()
+        .collect()
+}
+
+/// Returns the number of actions of each kind in the transactions signed by each account, with
+/// kinds named by [`ActionKind::as_str`].
+pub fn tx_dump_signer_action_matrix(
+    chunks: &BlockChunks,
+) -> HashMap<AccountId, HashMap<&'static str, u64>> {
+    let mut matrix: HashMap<AccountId, HashMap<&'static str, u64>> = HashMap::new();
+    for tx in chunks.transactions() {
+        let counts = matrix.entry(tx.transaction.signer_id.clone()).or_default();
+        for action in &tx.transaction.actions {
+            *counts.entry(ActionKind::from(action).as_str()).or_default() += 1;
+        }
+    }
+    matrix
+}
+
+/// Economic snapshot of the transactions included in a block.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct TxDumpSummary {
+    /// Number of transactions in the block.
+    pub num_transactions: u64,
+    /// Gas attached to all function calls.
+    pub total_gas: Gas,
+    /// Deposit attached to all transfers and function calls.
+    pub total_deposit: Balance,
+}
+
+/// Returns the [`TxDumpSummary`] of the chunks. Totals saturate instead of overflowing.
+pub fn tx_dump_summary(chunks: &BlockChunks) -> TxDumpSummary {
+    let mut summary = TxDumpSummary::default();
+    for tx in chunks.transactions() {
+        let (gas, deposit) = attached_gas_and_deposit(tx);
+        summary.num_transactions += 1;
+        summary.total_gas = summary.total_gas.saturating_add(gas);
+        summary.total_deposit = summary.total_deposit.saturating_add(deposit);
+    }
+    summary
+}
+
+/// Returns the mean number of actions per transaction, or 0 if there are no transactions.
+pub fn tx_dump_avg_actions(chunks: &BlockChunks) -> f64 {
+    let num_transactions = chunks.tx_count();
+    if num_transactions == 0 {
+        return 0.0;
+    }
+    let num_actions: usize = chunks.transactions().map(|tx| tx.transaction.actions.len()).sum();
+    num_actions as f64 / num_transactions as f64
+}
+
+/// Returns the gas attached to the function calls of the transactions of each chunk, in the
+/// order of shard ids, to spot imbalanced shard load. Totals saturate instead of overflowing.
+pub fn tx_dump_shard_gas(chunks: &BlockChunks) -> Vec<(ShardId, Gas)> {
+    chunks
+        .transactions_by_shard()
+        .map(|(shard_id, txs)| {
+            let gas = txs
+                .iter()
+                .fold(0, |total: Gas, tx| total.saturating_add(attached_gas_and_deposit(tx).0));
+            (shard_id, gas)
+        })
+        .collect()
+}
+
+/// Writes the transactions to `writer` as CSV, one row per transaction.
+///
+/// The columns are the signer, the receiver, the nonce, the number of actions, the deposit
+/// attached to transfers and function calls and the gas attached to function calls.
+pub fn tx_dump_csv(chunks: &BlockChunks, mut writer: impl Write) -> io::Result<()> {
+    writeln!(writer, "Signer,Receiver,Nonce,#Actions,Deposit,Gas")?;
+    for tx in chunks.transactions() {
+        let (gas, deposit) = attached_gas_and_deposit(tx);
+        let tx = &tx.transaction;
+        writeln!(
+            writer,
+            "{},{},{},{},{},{}",
+            csv_escape(tx.signer_id.as_ref()),
+            csv_escape(tx.receiver_id.as_ref()),
+            tx.nonce,
+            tx.actions.len(),
+            deposit,
+            gas
+        )?;
+    }
+    Ok(())
+}
+
+/// A line of [`tx_dump_ndjson`].
+#[derive(Serialize)]
+struct NdjsonTransaction {
+    shard_id: ShardId,
+    #[serde(flatten)]
+    transaction: SignedTransactionView,
+}
+
+/// Writes the transactions to `writer` as newline-delimited JSON, one object per line.
+///
+/// Each object is the RPC view of the transaction, which includes its hash, with the shard id
+/// of its chunk added. Unlike a JSON array, the output can be consumed line by line.
+pub fn tx_dump_ndjson(chunks: &BlockChunks, mut writer: impl Write) -> io::Result<()> {
+    for (shard_id, txs) in chunks.transactions_by_shard() {
+        for tx in txs {
+            let line = NdjsonTransaction { shard_id, transaction: tx.clone().into() };
+            serde_json::to_writer(&mut writer, &line)?;
+            writeln!(writer)?;
+        }
+    }
+    Ok(())
+}
+
+/// Quotes a CSV field if it contains a delimiter, a quote or a line break.
+fn csv_escape(field: &str) -> Cow<'_, str> {
+    if field.contains(|c: char| matches!(c, ',' | '"' | '\n' | '\r')) {
+        Cow::Owned(format!("\"{}\"", field.replace('"', "\"\"")))
+    } else {
+        Cow::Borrowed(field)
+    }
+}
+
+/// Returns the gas attached to function calls and the deposit attached to transfers and
+/// function calls of `tx`, saturating on overflow.
+fn attached_gas_and_deposit(tx: &SignedTransaction) -> (Gas, Balance) {
+    let mut total_gas: Gas = 0;
+    let mut total_deposit: Balance = 0;
+    for action in &tx.transaction.actions {
+        match action {
+            Action::FunctionCall(FunctionCallAction { gas, deposit, .. }) => {
+                total_gas = total_gas.saturating_add(*gas);
+                total_deposit = total_deposit.saturating_add(*deposit);
+            }
+            Action::Transfer(TransferAction { deposit }) => {
+                total_deposit = total_deposit.saturating_add(*deposit);
+            }
+            _ => {}
+        }
+    }
+    (total_gas, total_deposit)
+}
+
+#[cfg(test)]
+mod test {
+    use near_chain::{ChainGenesis, ChainStore, ChainStoreAccess, Provenance, RuntimeAdapter};
+    use near_chain_configs::Genesis;
+    use near_client::test_utils::TestEnv;
+    use near_crypto::{InMemorySigner, KeyType};
+    use near_network::types::NetworkClientResponses;
+    use near_primitives::account::AccessKey;
+    use near_primitives::block::Block;
+    use near_primitives::hash::CryptoHash;
+    use near_primitives::runtime::config_store::RuntimeConfigStore;
+    use near_primitives::transaction::{
+        Action, AddKeyAction, CreateAccountAction, DeleteAccountAction, DeleteKeyAction,
+        DeployContractAction, ExecutionStatus, FunctionCallAction, SignedTransaction, StakeAction,
+        TransferAction,
+    };
+    use near_primitives::types::{AccountId, Balance, Gas, Nonce, NumShards};
+    use near_primitives::utils::get_num_seats_per_shard;
+    use near_store::test_utils::create_test_store;
+    use nearcore::config::GenesisExt;
+    use nearcore::{NightshadeRuntime, TrackedConfig};
+    use std::collections::{HashMap, HashSet};
+    use std::path::Path;
+    use std::sync::{Arc, Mutex};
+
+    use crate::tx_dump::{
+        csv_escape, find_busiest_block, print_tx_dump, tx_count, tx_dump, tx_dump_annotated,
+        tx_dump_avg_actions, tx_dump_by_action_kind, tx_dump_by_method, tx_dump_by_public_key,
+        tx_dump_by_shard, tx_dump_called_contracts, tx_dump_csv, tx_dump_deployments,
+        tx_dump_empty_action_txs, tx_dump_ndjson, tx_dump_nonces, tx_dump_per_signer,
+        tx_dump_shard_gas, tx_dump_signer_action_matrix, tx_dump_stake_changes, tx_dump_stream,
+        tx_dump_summary, tx_dump_to_receiver, tx_dump_top_by_gas, tx_dump_with_hashes,
+        tx_dump_with_outcomes, ActionKind, BlockChunks, TxDumpSummary,
+    };
+
+    const NUM_SHARDS: NumShards = 4;
+
+    fn setup_env() -> TestEnv {
+        let genesis = Genesis::test_sharded(
+            (0..NUM_SHARDS).map(|i| format!("test{}", i).parse().unwrap()).collect(),
+            1,
+            get_num_seats_per_shard(NUM_SHARDS, 1),
+        );
+        let runtime = NightshadeRuntime::test_with_runtime_config_store(
+            Path::new("."),
+            create_test_store(),
+            &genesis,
+            TrackedConfig::AllShards,
+            RuntimeConfigStore::test(),
+        );
+        TestEnv::builder(ChainGenesis::test()).runtime_adapters(vec![Arc::new(runtime)]).build()
+    }
+
+    fn genesis_hash(env: &TestEnv) -> CryptoHash {
+        *env.clients[0].chain.genesis().hash()
+    }
+
+    /// Signs a transaction with `actions` from `test{signer}` to `test{receiver}`.
+    fn tx(
+        signer: u64,
+        receiver: u64,
+        nonce: Nonce,
+        actions: Vec<Action>,
+        block_hash: CryptoHash,
+    ) -> SignedTransaction {
+        let signer_id = format!("test{}", signer);
+        let signer =
+            InMemorySigner::from_seed(signer_id.parse().unwrap(), KeyType::ED25519, &signer_id);
+        SignedTransaction::from_actions(
+            nonce,
+            signer.account_id.clone(),
+            format!("test{}", receiver).parse().unwrap(),
+            &signer,
+            actions,
+            block_hash,
+        )
+    }
+
+    fn transfer(deposit: Balance) -> Action {
+        Action::Transfer(TransferAction { deposit })
+    }
+
+    fn function_call(method_name: &str, gas: Gas, deposit: Balance) -> Action {
+        Action::FunctionCall(FunctionCallAction {
+            method_name: method_name.to_string(),
+            args: vec![],
+            gas,
+            deposit,
+        })
+    }
+
+    fn sorted_hashes<'a>(txs: impl IntoIterator<Item = &'a SignedTransaction>) -> Vec<CryptoHash> {
+        let mut hashes = txs.into_iter().map(|tx| tx.get_hash()).collect::<Vec<_>>();
+        hashes.sort();
+        hashes
+    }
+
+    /// Submits `txs` and produces blocks until they are included in chunks.
+    fn produce_blocks_with_txs(env: &mut TestEnv, txs: Vec<SignedTransaction>) -> Vec<Block> {
+        for tx in txs {
+            let response = env.clients[0].process_tx(tx, false, false);
+            assert_eq!(response, NetworkClientResponses::ValidTx);
+        }
+        let mut blocks = vec![];
+        for height in 1..=5 {
+            let block = env.clients[0].produce_block(height).unwrap().unwrap();
+            env.process_block(0, block.clone(), Provenance::PRODUCED);
+            blocks.push(block);
+        }
+        blocks
+    }
+
+    #[test]
+    fn test_tx_dump() {
+        let mut env = setup_env();
+        let genesis_hash = genesis_hash(&env);
+        let txs = (0..NUM_SHARDS)
+            .map(|i| tx(i, (i + 1) % NUM_SHARDS, 1, vec![transfer(100)], genesis_hash))
+            .collect::<Vec<_>>();
+        let expected = sorted_hashes(&txs);
+        let blocks = produce_blocks_with_txs(&mut env, txs);
+
+        let chain_store = env.clients[0].chain.store();
+        let mut dumped = vec![];
+        for block in &blocks {
+            let txs = tx_dump(chain_store, block);
+            // Transactions follow the order of the chunks in the block.
+            let height = block.header().height();
+            let in_chunk_order = block
+                .chunks()
+                .iter()
+                .filter(|chunk_header| chunk_header.height_included() == height)
+                .flat_map(|chunk_header| {
+                    chain_store
+                        .get_chunk(&chunk_header.chunk_hash())
+                        .unwrap()
+                        .transactions()
+                        .to_vec()
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(txs, in_chunk_order);
+            dumped.extend(txs);
+        }
+        assert_eq!(sorted_hashes(&dumped), expected);
+    }
+
+    #[test]
+    fn test_tx_count() {
+        let mut env = setup_env();
+        let genesis_hash = genesis_hash(&env);
+        let txs = (0..NUM_SHARDS)
+            .flat_map(|i| {
+                (1..=2).map(move |nonce| {
+                    tx(i, (i + 1) % NUM_SHARDS, nonce, vec![transfer(100)], genesis_hash)
+                })
+            })
+            .collect::<Vec<_>>();
+        let num_txs = txs.len() as u64;
+        let blocks = produce_blocks_with_txs(&mut env, txs);
+
+        let chain_store = env.clients[0].chain.store();
+        let mut total = 0;
+        for block in &blocks {
+            let count = tx_count(chain_store, block);
+            assert_eq!(count, tx_dump(chain_store, block).len() as u64);
+            total += count;
+        }
+        assert_eq!(total, num_txs);
+    }
+
+    #[test]
+    fn test_block_chunks_transactions_match_tx_dump() {
+        let mut env = setup_env();
+        let genesis_hash = genesis_hash(&env);
+        let txs = (0..NUM_SHARDS)
+            .flat_map(|i| {
+                (1..=2).map(move |nonce| {
+                    tx(i, (i + 1) % NUM_SHARDS, nonce, vec![transfer(100)], genesis_hash)
+                })
+            })
+            .collect::<Vec<_>>();
+        let blocks = produce_blocks_with_txs(&mut env, txs);
+
+        let chain_store = env.clients[0].chain.store();
+        for block in &blocks {
+            let block_chunks = BlockChunks::new(chain_store, block);
+            let borrowed = block_chunks.transactions().collect::<Vec<_>>();
+            let cloned = tx_dump(chain_store, block);
+            assert_eq!(borrowed, cloned.iter().collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn test_tx_dump_is_deterministic() {
+        let mut env = setup_env();
+        let genesis_hash = genesis_hash(&env);
+        let txs = (0..NUM_SHARDS)
+            .flat_map(|i| {
+                (1..=3).map(move |nonce| {
+                    tx(i, (i + 1) % NUM_SHARDS, nonce, vec![transfer(nonce.into())], genesis_hash)
+                })
+            })
+            .collect::<Vec<_>>();
+        let blocks = produce_blocks_with_txs(&mut env, txs);
+
+        let chain_store = env.clients[0].chain.store();
+        let dump = |block: &Block| {
+            let txs = tx_dump(chain_store, block);
+            let mut output = vec![];
+            print_tx_dump(&txs, &mut output).unwrap();
+            (txs, String::from_utf8(output).unwrap())
+        };
+        let mut num_dumped = 0;
+        for block in &blocks {
+            let (txs, output) = dump(block);
+            for _ in 0..5 {
+                assert_eq!(dump(block), (txs.clone(), output.clone()));
+            }
+            assert_eq!(output.lines().count(), txs.len());
+            num_dumped += txs.len();
+        }
+        assert_eq!(num_dumped, 12);
+    }
+
+    #[test]
+    fn test_tx_dump_with_hashes() {
+        let mut env = setup_env();
+        let genesis_hash = genesis_hash(&env);
+        let txs = (0..NUM_SHARDS)
+            .map(|i| tx(i, (i + 1) % NUM_SHARDS, 1, vec![transfer(100)], genesis_hash))
+            .collect::<Vec<_>>();
+        let expected = sorted_hashes(&txs);
+        let blocks = produce_blocks_with_txs(&mut env, txs);
+
+        let chain_store = env.clients[0].chain.store();
+        let mut dumped = vec![];
+        for block in &blocks {
+            for (hash, tx) in tx_dump_with_hashes(chain_store, block) {
+                assert_eq!(hash, tx.get_hash());
+                dumped.push(tx);
+            }
+        }
+        assert_eq!(sorted_hashes(&dumped), expected);
+    }
+
+    #[test]
+    fn test_tx_dump_stream() {
+        let mut env = setup_env();
+        let genesis_hash = genesis_hash(&env);
+        let txs = (0..NUM_SHARDS)
+            .flat_map(|i| {
+                (1..=2).map(move |nonce| {
+                    tx(i, (i + 1) % NUM_SHARDS, nonce, vec![transfer(100)], genesis_hash)
+                })
+            })
+            .collect::<Vec<_>>();
+        let blocks = produce_blocks_with_txs(&mut env, txs.clone());
+
+        let chain_store = env.clients[0].chain.store();
+        let mut num_calls = 0;
+        let mut streamed = vec![];
+        for block in &blocks {
+            let mut block_txs = vec![];
+            tx_dump_stream(chain_store, block, |shard_id, tx| {
+                num_calls += 1;
+                block_txs.push((shard_id, tx.clone()));
+            });
+            let by_shard = tx_dump_by_shard(chain_store, block)
+                .into_iter()
+                .flat_map(|(shard_id, txs)| txs.into_iter().map(move |tx| (shard_id, tx)))
+                .collect::<Vec<_>>();
+            assert_eq!(block_txs, by_shard);
+            streamed.extend(block_txs.into_iter().map(|(_, tx)| tx));
+        }
+        assert_eq!(num_calls, txs.len());
+        assert_eq!(sorted_hashes(&streamed), sorted_hashes(&txs));
+    }
+
+    #[test]
+    fn test_tx_dump_with_outcomes() {
+        let mut env = setup_env();
+        let genesis_hash = genesis_hash(&env);
+        let txs = (0..NUM_SHARDS)
+            .map(|i| tx(i, (i + 1) % NUM_SHARDS, 1, vec![transfer(100)], genesis_hash))
+            .collect::<Vec<_>>();
+        let expected = sorted_hashes(&txs);
+        let blocks = produce_blocks_with_txs(&mut env, txs);
+
+        let chain_store = env.clients[0].chain.store();
+        let mut dumped = vec![];
+        for block in &blocks {
+            for (tx, outcome) in tx_dump_with_outcomes(chain_store, block) {
+                let outcome = outcome.expect("outcome of an applied transaction is recorded");
+                assert_eq!(outcome.executor_id, tx.transaction.signer_id);
+                assert!(
+                    matches!(outcome.status, ExecutionStatus::SuccessReceiptId(_)),
+                    "{:?}",
+                    outcome.status
+                );
+                dumped.push(tx);
+            }
+        }
+        assert_eq!(sorted_hashes(&dumped), expected);
+    }
+
+    #[test]
+    fn test_tx_dump_annotated() {
+        let mut env = setup_env();
+        let genesis_hash = genesis_hash(&env);
+        let txs = (0..NUM_SHARDS)
+            .map(|i| tx(i, (i + 1) % NUM_SHARDS, 1, vec![transfer(100)], genesis_hash))
+            .collect::<Vec<_>>();
+        let expected = sorted_hashes(&txs);
+        let blocks = produce_blocks_with_txs(&mut env, txs);
+
+        let chain_store = env.clients[0].chain.store();
+        let mut dumped = vec![];
+        for block in &blocks {
+            for (height, timestamp, tx) in tx_dump_annotated(chain_store, block) {
+                assert_eq!(height, block.header().height());
+                assert_eq!(timestamp, block.header().raw_timestamp());
+                dumped.push(tx);
+            }
+        }
+        assert_eq!(sorted_hashes(&dumped), expected);
+    }
+
+    #[test]
+    fn test_tx_dump_by_method() {
+        let mut env = setup_env();
+        let genesis_hash = genesis_hash(&env);
+        let txs = vec![
+            tx(0, 1, 1, vec![function_call("foo", 1_000_000, 0)], genesis_hash),
+            tx(1, 2, 1, vec![function_call("bar", 1_000_000, 0)], genesis_hash),
+            tx(2, 3, 1, vec![transfer(1), function_call("foo", 1_000_000, 0)], genesis_hash),
+            tx(3, 0, 1, vec![transfer(1)], genesis_hash),
+        ];
+        let expected = sorted_hashes([&txs[0], &txs[2]]);
+        let blocks = produce_blocks_with_txs(&mut env, txs);
+
+        let chain_store = env.clients[0].chain.store();
+        let dumped = blocks
+            .iter()
+            .flat_map(|block| tx_dump_by_method(&BlockChunks::new(chain_store, block), "foo"))
+            .collect::<Vec<_>>();
+        assert_eq!(sorted_hashes(&dumped), expected);
+    }
+
+    #[test]
+    fn test_tx_dump_called_contracts() {
+        let mut env = setup_env();
+        let genesis_hash = genesis_hash(&env);
+        let txs = vec![
+            tx(0, 1, 1, vec![function_call("foo", 1_000_000, 0)], genesis_hash),
+            tx(1, 2, 1, vec![transfer(1)], genesis_hash),
+            tx(2, 1, 1, vec![transfer(1), function_call("bar", 1_000_000, 0)], genesis_hash),
+            tx(3, 0, 1, vec![transfer(1), function_call("foo", 1_000_000, 0)], genesis_hash),
+        ];
+        let blocks = produce_blocks_with_txs(&mut env, txs);
+
+        let chain_store = env.clients[0].chain.store();
+        let called = blocks
+            .iter()
+            .flat_map(|block| tx_dump_called_contracts(&BlockChunks::new(chain_store, block)))
+            .collect::<HashSet<_>>();
+        let expected = HashSet::from(["test0".parse().unwrap(), "test1".parse().unwrap()]);
+        assert_eq!(called, expected);
+    }
+
+    #[test]
+    fn test_tx_dump_to_receiver() {
+        let mut env = setup_env();
+        let genesis_hash = genesis_hash(&env);
+        let txs = vec![
+            tx(0, 1, 1, vec![transfer(1)], genesis_hash),
+            tx(1, 2, 1, vec![transfer(1)], genesis_hash),
+            tx(2, 1, 1, vec![function_call("foo", 1_000_000, 0)], genesis_hash),
+            tx(3, 0, 1, vec![transfer(1)], genesis_hash),
+        ];
+        let expected = sorted_hashes([&txs[0], &txs[2]]);
+        let blocks = produce_blocks_with_txs(&mut env, txs);
+
+        let chain_store = env.clients[0].chain.store();
+        let receiver_id = "test1".parse().unwrap();
+        let dumped = blocks
+            .iter()
+            .flat_map(|block| {
+                tx_dump_to_receiver(&BlockChunks::new(chain_store, block), &receiver_id)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(sorted_hashes(&dumped), expected);
+    }
+
+    #[test]
+    fn test_tx_dump_empty_action_txs() {
+        let mut env = setup_env();
+        let genesis_hash = genesis_hash(&env);
+        let txs = vec![
+            tx(0, 1, 1, vec![transfer(1)], genesis_hash),
+            tx(1, 2, 1, vec![], genesis_hash),
+            tx(2, 1, 1, vec![function_call("foo", 1_000_000, 0)], genesis_hash),
+        ];
+        let expected = sorted_hashes([&txs[1]]);
+        let blocks = produce_blocks_with_txs(&mut env, txs);
+
+        let chain_store = env.clients[0].chain.store();
+        let dumped = blocks
+            .iter()
+            .flat_map(|block| tx_dump_empty_action_txs(&BlockChunks::new(chain_store, block)))
+            .collect::<Vec<_>>();
+        assert_eq!(sorted_hashes(&dumped), expected);
+    }
+
+    #[test]
+    fn test_tx_dump_top_by_gas() {
+        let mut env = setup_env();
+        let genesis_hash = genesis_hash(&env);
+        let txs = vec![
+            tx(0, 1, 1, vec![function_call("foo", 3_000_000, 0)], genesis_hash),
+            tx(1, 2, 1, vec![function_call("foo", 1_000_000, 0)], genesis_hash),
+            tx(2, 3, 1, vec![transfer(1), function_call("foo", 3_000_000, 0)], genesis_hash),
+            tx(3, 0, 1, vec![transfer(1)], genesis_hash),
+        ];
+        let mut expected = vec![txs[0].get_hash(), txs[2].get_hash()];
+        expected.sort();
+        expected.push(txs[1].get_hash());
+        let blocks = produce_blocks_with_txs(&mut env, txs);
+
+        let chain_store = env.clients[0].chain.store();
+        let chunks = blocks
+            .iter()
+            .map(|block| BlockChunks::new(chain_store, block))
+            .find(|chunks| chunks.tx_count() == 4)
+            .expect("all transactions are included in the same block");
+        let top = tx_dump_top_by_gas(&chunks, 3);
+        assert_eq!(top.iter().map(|tx| tx.get_hash()).collect::<Vec<_>>(), expected);
+        assert_eq!(tx_dump_top_by_gas(&chunks, 10).len(), 4);
+    }
+
+    #[test]
+    fn test_tx_dump_per_signer() {
+        let mut env = setup_env();
+        let genesis_hash = genesis_hash(&env);
+        let txs = [(0, 3), (1, 2)]
+            .into_iter()
+            .flat_map(|(signer, num_txs)| {
+                (1..=num_txs)
+                    .map(move |nonce| tx(signer, 2, nonce, vec![transfer(1)], genesis_hash))
+            })
+            .collect::<Vec<_>>();
+        let blocks = produce_blocks_with_txs(&mut env, txs);
+
+        let chain_store = env.clients[0].chain.store();
+        let mut counts = HashMap::new();
+        for block in &blocks {
+            for (signer_id, count) in tx_dump_per_signer(&BlockChunks::new(chain_store, block)) {
+                *counts.entry(signer_id).or_default() += count;
+            }
+        }
+        let expected =
+            HashMap::from([("test0".parse().unwrap(), 3), ("test1".parse().unwrap(), 2)]);
+        assert_eq!(counts, expected);
+    }
+
+    #[test]
+    fn test_tx_dump_signer_action_matrix() {
+        let mut env = setup_env();
+        let genesis_hash = genesis_hash(&env);
+        let txs = vec![
+            tx(0, 1, 1, vec![transfer(1), transfer(2)], genesis_hash),
+            tx(0, 2, 2, vec![function_call("foo", 1_000_000, 0)], genesis_hash),
+            tx(1, 2, 1, vec![transfer(1), function_call("foo", 1_000_000, 0)], genesis_hash),
+            tx(1, 3, 2, vec![Action::CreateAccount(CreateAccountAction {})], genesis_hash),
+        ];
+        let blocks = produce_blocks_with_txs(&mut env, txs);
+
+        let chain_store = env.clients[0].chain.store();
+        let mut matrix: HashMap<AccountId, HashMap<&'static str, u64>> = HashMap::new();
+        for block in &blocks {
+            let chunks = BlockChunks::new(chain_store, block);
+            for (signer_id, counts) in tx_dump_signer_action_matrix(&chunks) {
+                let total = matrix.entry(signer_id).or_default();
+                for (kind, count) in counts {
+                    *total.entry(kind).or_default() += count;
+                }
+            }
+        }
+        let expected = HashMap::from([
+            ("test0".parse().unwrap(), HashMap::from([("Transfer", 2), ("FunctionCall", 1)])),
+            (
+                "test1".parse().unwrap(),
+                HashMap::from([("Transfer", 1), ("FunctionCall", 1), ("CreateAccount", 1)]),
+            ),
+        ]);
+        assert_eq!(matrix, expected);
+    }
+
+    #[test]
+    fn test_tx_dump_deployments() {
+        let mut env = setup_env();
+        let genesis_hash = genesis_hash(&env);
+        let code = vec![1, 2, 3];
+        let txs = vec![
+            tx(0, 1, 1, vec![transfer(1)], genesis_hash),
+            tx(
+                1,
+                1,
+                1,
+                vec![Action::DeployContract(DeployContractAction { code: code.clone() })],
+                genesis_hash,
+            ),
+        ];
+        let blocks = produce_blocks_with_txs(&mut env, txs);
+
+        let chain_store = env.clients[0].chain.store();
+        let deployments = blocks
+            .iter()
+            .flat_map(|block| tx_dump_deployments(&BlockChunks::new(chain_store, block)))
+            .collect::<Vec<_>>();
+        assert_eq!(deployments, vec![("test1".parse().unwrap(), CryptoHash::hash_bytes(&code))]);
+    }
+
+    #[test]
+    fn test_tx_dump_stake_changes() {
+        let mut env = setup_env();
+        let genesis_hash = genesis_hash(&env);
+        let public_key =
+            InMemorySigner::from_seed("test0".parse().unwrap(), KeyType::ED25519, "test0")
+                .public_key;
+        let stake = |stake| Action::Stake(StakeAction { stake, public_key: public_key.clone() });
+        let txs = vec![
+            tx(0, 0, 1, vec![stake(100)], genesis_hash),
+            tx(0, 0, 2, vec![stake(50)], genesis_hash),
+            tx(1, 1, 1, vec![stake(10), transfer(1)], genesis_hash),
+        ];
+        let blocks = produce_blocks_with_txs(&mut env, txs);
+
+        let chain_store = env.clients[0].chain.store();
+        let mut stakes = HashMap::new();
+        for block in &blocks {
+            for (key, stake) in tx_dump_stake_changes(&BlockChunks::new(chain_store, block)) {
+                *stakes.entry(key).or_default() += stake;
+            }
+        }
+        let expected = HashMap::from([
+            (("test0".parse().unwrap(), public_key.clone()), 150),
+            (("test1".parse().unwrap(), public_key.clone()), 10),
+        ]);
+        assert_eq!(stakes, expected);
+    }
+
+    #[test]
+    fn test_tx_dump_nonces() {
+        let mut env = setup_env();
+        let genesis_hash = genesis_hash(&env);
+        let txs = vec![
+            tx(0, 1, 1, vec![transfer(1)], genesis_hash),
+            tx(0, 2, 2, vec![transfer(1)], genesis_hash),
+            tx(0, 3, 5, vec![transfer(1)], genesis_hash),
+            tx(1, 2, 1, vec![transfer(1)], genesis_hash),
+        ];
+        let blocks = produce_blocks_with_txs(&mut env, txs);
+
+        let chain_store = env.clients[0].chain.store();
+        let mut nonces: HashMap<AccountId, Vec<Nonce>> = HashMap::new();
+        for block in &blocks {
+            for ((signer_id, public_key), key_nonces) in
+                tx_dump_nonces(&BlockChunks::new(chain_store, block))
+            {
+                let signer = InMemorySigner::from_seed(
+                    signer_id.clone(),
+                    KeyType::ED25519,
+                    signer_id.as_ref(),
+                );
+                assert_eq!(public_key, signer.public_key);
+                nonces.entry(signer_id).or_default().extend(key_nonces);
+            }
+        }
+        for key_nonces in nonces.values_mut() {
+            key_nonces.sort();
+        }
+        let expected = HashMap::from([
+            ("test0".parse().unwrap(), vec![1, 2, 5]),
+            ("test1".parse().unwrap(), vec![1]),
+        ]);
+        assert_eq!(nonces, expected);
+    }
+
+    #[test]
+    fn test_tx_dump_by_public_key() {
+        let mut env = setup_env();
+        let genesis_hash = genesis_hash(&env);
+        let public_key = |seed: &str| {
+            InMemorySigner::from_seed(seed.parse().unwrap(), KeyType::ED25519, seed).public_key
+        };
+        let add_key = |public_key| {
+            Action::AddKey(AddKeyAction { public_key, access_key: AccessKey::full_access() })
+        };
+        let txs = vec![
+            tx(0, 1, 1, vec![transfer(1)], genesis_hash),
+            tx(2, 3, 1, vec![add_key(public_key("alice"))], genesis_hash),
+            tx(2, 3, 2, vec![add_key(public_key("bob"))], genesis_hash),
+            tx(
+                3,
+                0,
+                1,
+                vec![
+                    transfer(1),
+                    Action::DeleteKey(DeleteKeyAction { public_key: public_key("alice") }),
+                ],
+                genesis_hash,
+            ),
+        ];
+        let blocks = produce_blocks_with_txs(&mut env, txs.clone());
+
+        let chain_store = env.clients[0].chain.store();
+        let chunks =
+            blocks.iter().map(|block| BlockChunks::new(chain_store, block)).collect::<Vec<_>>();
+        for (seed, expected) in [("alice", vec![1, 3]), ("bob", vec![2]), ("test0", vec![0])] {
+            let public_key = public_key(seed);
+            let dumped = chunks
+                .iter()
+                .flat_map(|chunks| tx_dump_by_public_key(chunks, &public_key))
+                .collect::<Vec<_>>();
+            assert_eq!(
+                sorted_hashes(&dumped),
+                sorted_hashes(expected.into_iter().map(|i| &txs[i])),
+                "{}",
+                seed
+            );
+        }
+    }
+
+    #[test]
+    fn test_tx_dump_by_action_kind() {
+        let mut env = setup_env();
+        let genesis_hash = genesis_hash(&env);
+        let public_key =
+            InMemorySigner::from_seed("test0".parse().unwrap(), KeyType::ED25519, "test0")
+                .public_key;
+        let txs = vec![
+            tx(0, 1, 1, vec![Action::CreateAccount(CreateAccountAction {})], genesis_hash),
+            tx(
+                0,
+                1,
+                2,
+                vec![Action::DeployContract(DeployContractAction { code: vec![0; 10] })],
+                genesis_hash,
+            ),
+            tx(1, 2, 1, vec![function_call("foo", 1_000_000, 0)], genesis_hash),
+            tx(1, 2, 2, vec![transfer(1), function_call("foo", 1_000_000, 0)], genesis_hash),
+            tx(
+                2,
+                3,
+                1,
+                vec![Action::Stake(StakeAction { stake: 1, public_key: public_key.clone() })],
+                genesis_hash,
+            ),
+            tx(
+                2,
+                3,
+                2,
+                vec![Action::AddKey(AddKeyAction {
+                    public_key: public_key.clone(),
+                    access_key: AccessKey::full_access(),
+                })],
+                genesis_hash,
+            ),
+            tx(3, 0, 1, vec![Action::DeleteKey(DeleteKeyAction { public_key })], genesis_hash),
+            tx(
+                3,
+                0,
+                2,
+                vec![Action::DeleteAccount(DeleteAccountAction {
+                    beneficiary_id: "test3".parse().unwrap(),
+                })],
+                genesis_hash,
+            ),
+        ];
+        let blocks = produce_blocks_with_txs(&mut env, txs.clone());
+
+        let chain_store = env.clients[0].chain.store();
+        let chunks =
+            blocks.iter().map(|block| BlockChunks::new(chain_store, block)).collect::<Vec<_>>();
+        for (kind, expected) in [
+            (ActionKind::CreateAccount, vec![0]),
+            (ActionKind::DeployContract, vec![1]),
+            (ActionKind::FunctionCall, vec![2, 3]),
+            (ActionKind::Transfer, vec![3]),
+            (ActionKind::Stake, vec![4]),
+            (ActionKind::AddKey, vec![5]),
+            (ActionKind::DeleteKey, vec![6]),
+            (ActionKind::DeleteAccount, vec![7]),
+        ] {
+            let dumped = chunks
+                .iter()
+                .flat_map(|chunks| tx_dump_by_action_kind(chunks, kind))
+                .collect::<Vec<_>>();
+            let expected = sorted_hashes(expected.into_iter().map(|i| &txs[i]));
+            assert_eq!(sorted_hashes(&dumped), expected, "{:?}", kind);
+        }
+    }
+
+    #[test]
+    fn test_tx_dump_summary() {
+        let mut env = setup_env();
+        let genesis_hash = genesis_hash(&env);
+        let txs = vec![
+            tx(0, 1, 1, vec![function_call("foo", 1_000_000, 10)], genesis_hash),
+            tx(1, 2, 1, vec![transfer(100), function_call("bar", 2_000_000, 0)], genesis_hash),
+            tx(2, 3, 1, vec![transfer(1000)], genesis_hash),
+        ];
+        let blocks = produce_blocks_with_txs(&mut env, txs);
+
+        let chain_store = env.clients[0].chain.store();
+        let mut total = TxDumpSummary::default();
+        for block in &blocks {
+            let summary = tx_dump_summary(&BlockChunks::new(chain_store, block));
+            total.num_transactions += summary.num_transactions;
+            total.total_gas += summary.total_gas;
+            total.total_deposit += summary.total_deposit;
+        }
+        assert_eq!(
+            total,
+            TxDumpSummary { num_transactions: 3, total_gas: 3_000_000, total_deposit: 1110 }
+        );
+    }
+
+    #[test]
+    fn test_tx_dump_avg_actions() {
+        let mut env = setup_env();
+        let genesis_hash = genesis_hash(&env);
+        let txs = vec![
+            tx(0, 1, 1, vec![transfer(1)], genesis_hash),
+            tx(1, 2, 1, vec![transfer(1), transfer(2)], genesis_hash),
+            tx(
+                2,
+                3,
+                1,
+                vec![transfer(1), transfer(2), function_call("foo", 1_000_000, 0)],
+                genesis_hash,
+            ),
+        ];
+        let blocks = produce_blocks_with_txs(&mut env, txs);
+
+        let chain_store = env.clients[0].chain.store();
+        let mut num_actions = 0.0;
+        for block in &blocks {
+            let chunks = BlockChunks::new(chain_store, block);
+            let avg_actions = tx_dump_avg_actions(&chunks);
+            if chunks.tx_count() == 0 {
+                assert_eq!(avg_actions, 0.0);
+            }
+            num_actions += avg_actions * chunks.tx_count() as f64;
+        }
+        assert_eq!(num_actions, 6.0);
+    }
+
+    #[test]
+    fn test_find_busiest_block() {
+        let mut env = setup_env();
+        let genesis_hash = genesis_hash(&env);
+        let txs = (0..NUM_SHARDS)
+            .map(|i| tx(i, (i + 1) % NUM_SHARDS, 1, vec![transfer(100)], genesis_hash))
+            .collect::<Vec<_>>();
+        let blocks = produce_blocks_with_txs(&mut env, txs);
+
+        let chain_store = env.clients[0].chain.store();
+        let mut expected = (0, 0);
+        for block in &blocks {
+            let count = tx_count(chain_store, block);
+            if count > expected.1 {
+                expected = (block.header().height(), count);
+            }
+        }
+        assert!(expected.1 > 0);
+        let last_height = blocks.last().unwrap().header().height();
+        assert_eq!(find_busiest_block(chain_store, 0, last_height), Some(expected));
+        assert_eq!(find_busiest_block(chain_store, last_height + 1, last_height + 10), None);
+    }
+
+    #[test]
+    fn test_tx_dump_shard_gas() {
+        let mut env = setup_env();
+        let genesis_hash = genesis_hash(&env);
+        let txs = vec![
+            tx(0, 1, 1, vec![function_call("foo", 1_000_000, 0)], genesis_hash),
+            tx(0, 1, 2, vec![function_call("foo", 2_000_000, 0), transfer(1)], genesis_hash),
+            tx(1, 2, 1, vec![function_call("bar", 5_000_000, 0)], genesis_hash),
+        ];
+        let blocks = produce_blocks_with_txs(&mut env, txs);
+
+        let chain_store = env.clients[0].chain.store();
+        let runtime_adapter = &env.clients[0].runtime_adapter;
+        let epoch_id = blocks[0].header().epoch_id();
+        let mut expected = HashMap::new();
+        for (signer_id, gas) in [("test0", 3_000_000), ("test1", 5_000_000)] {
+            let shard_id = runtime_adapter
+                .account_id_to_shard_id(&signer_id.parse().unwrap(), epoch_id)
+                .unwrap();
+            *expected.entry(shard_id).or_insert(0) += gas;
+        }
+
+        let mut gas_by_shard = HashMap::new();
+        for block in &blocks {
+            let shard_gas = tx_dump_shard_gas(&BlockChunks::new(chain_store, block));
+            let shard_ids = shard_gas.iter().map(|(shard_id, _)| *shard_id).collect::<Vec<_>>();
+            assert!(shard_ids.windows(2).all(|w| w[0] < w[1]), "{:?}", shard_ids);
+            for (shard_id, gas) in shard_gas.into_iter().filter(|(_, gas)| *gas > 0) {
+                *gas_by_shard.entry(shard_id).or_insert(0) += gas;
+            }
+        }
+        assert_eq!(gas_by_shard, expected);
+    }
+
+    #[test]
+    fn test_tx_dump_by_shard() {
+        let mut env = setup_env();
+        let genesis_hash = genesis_hash(&env);
+        let txs = (0..NUM_SHARDS)
+            .map(|i| tx(i, (i + 1) % NUM_SHARDS, 1, vec![transfer(100)], genesis_hash))
+            .collect::<Vec<_>>();
+        let blocks = produce_blocks_with_txs(&mut env, txs.clone());
+
+        let chain_store = env.clients[0].chain.store();
+        let runtime_adapter = &env.clients[0].runtime_adapter;
+        let mut dumped = vec![];
+        for block in &blocks {
+            let by_shard = tx_dump_by_shard(chain_store, block);
+            let shard_ids = by_shard.iter().map(|(shard_id, _)| *shard_id).collect::<Vec<_>>();
+            assert!(shard_ids.windows(2).all(|w| w[0] < w[1]), "{:?}", shard_ids);
+            for (shard_id, shard_txs) in by_shard {
+                for tx in shard_txs {
+                    let signer_shard_id = runtime_adapter
+                        .account_id_to_shard_id(
+                            &tx.transaction.signer_id,
+                            block.header().epoch_id(),
+                        )
+                        .unwrap();
+                    assert_eq!(signer_shard_id, shard_id);
+                    dumped.push(tx);
+                }
+            }
+        }
+        assert_eq!(sorted_hashes(&dumped), sorted_hashes(&txs));
+    }
+
+    #[test]
+    fn test_tx_dump_csv() {
+        let mut env = setup_env();
+        let genesis_hash = genesis_hash(&env);
+        let txs = vec![
+            tx(0, 1, 7, vec![transfer(100), function_call("foo", 1_000_000, 10)], genesis_hash),
+            tx(1, 2, 1, vec![transfer(1)], genesis_hash),
+        ];
+        let blocks = produce_blocks_with_txs(&mut env, txs);
+
+        let chain_store = env.clients[0].chain.store();
+        let mut rows = vec![];
+        for block in &blocks {
+            let mut csv = vec![];
+            tx_dump_csv(&BlockChunks::new(chain_store, block), &mut csv).unwrap();
+            let csv = String::from_utf8(csv).unwrap();
+            let mut lines = csv.lines();
+            assert_eq!(lines.next(), Some("Signer,Receiver,Nonce,#Actions,Deposit,Gas"));
+            rows.extend(lines.map(str::to_string));
+        }
+        rows.sort();
+        assert_eq!(rows, vec!["test0,test1,7,2,110,1000000", "test1,test2,1,1,1,0"]);
+    }
+
+    #[test]
+    fn test_tx_dump_ndjson() {
+        let mut env = setup_env();
+        let genesis_hash = genesis_hash(&env);
+        let txs = vec![
+            tx(0, 1, 7, vec![transfer(100), function_call("foo", 1_000_000, 10)], genesis_hash),
+            tx(1, 2, 1, vec![transfer(1)], genesis_hash),
+        ];
+        let sample_hash = txs[0].get_hash();
+        let blocks = produce_blocks_with_txs(&mut env, txs);
+
+        let chain_store = env.clients[0].chain.store();
+        let mut objects = vec![];
+        for block in &blocks {
+            let mut ndjson = vec![];
+            tx_dump_ndjson(&BlockChunks::new(chain_store, block), &mut ndjson).unwrap();
+            for line in String::from_utf8(ndjson).unwrap().lines() {
+                objects.push(serde_json::from_str::<serde_json::Value>(line).unwrap());
+            }
+        }
+        assert_eq!(objects.len(), 2);
+        let sample = objects
+            .iter()
+            .find(|object| object["hash"] == sample_hash.to_string())
+            .expect("transaction should be dumped");
+        assert!(sample["shard_id"].is_u64());
+        assert_eq!(sample["signer_id"], "test0");
+        assert_eq!(sample["receiver_id"], "test1");
+        assert_eq!(sample["nonce"], 7);
+        assert_eq!(sample["actions"].as_array().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_csv_escape() {
+        assert_eq!(csv_escape("alice.near"), "alice.near");
+        assert_eq!(csv_escape("a,b"), "\"a,b\"");
+        assert_eq!(csv_escape("a\"b"), "\"a\"\"b\"");
+    }
+
+    #[test]
+    fn test_block_chunks_reads_each_chunk_once() {
+        let mut env = setup_env();
+        let genesis_hash = genesis_hash(&env);
+        let txs = (0..NUM_SHARDS)
+            .map(|i| tx(i, (i + 1) % NUM_SHARDS, 1, vec![transfer(100)], genesis_hash))
+            .collect::<Vec<_>>();
+        let blocks = produce_blocks_with_txs(&mut env, txs);
+
+        let chain_store = env.clients[0].chain.store();
+        let store = chain_store.store();
+        let genesis_height = chain_store.get_genesis_height();
+        let block = blocks.last().unwrap();
+        let reads = Mutex::new(HashMap::new());
+        let chunks = BlockChunks::with_fetcher(block, |chunk_hash| {
+            *reads.lock().unwrap().entry(chunk_hash.clone()).or_insert(0) += 1;
+            ChainStore::new(store.clone(), genesis_height, false).get_chunk(chunk_hash).ok()
+        });
+        tx_dump_summary(&chunks);
+        tx_dump_by_method(&chunks, "foo");
+        tx_dump_csv(&chunks, std::io::sink()).unwrap();
+
+        let num_new_chunks = block
+            .chunks()
+            .iter()
+            .filter(|chunk_header| chunk_header.height_included() == block.header().height())
+            .count();
+        assert!(num_new_chunks > 0);
+        let reads = reads.into_inner().unwrap();
+        assert_eq!(reads.len(), num_new_chunks);
+        assert!(reads.values().all(|count| *count == 1), "{:?}", reads);
+        assert_eq!(chunks.transactions_by_shard().count(), num_new_chunks);
+    }
+
+    #[test]
+    fn test_receipt_dump() {
+        let mut env = setup_env();
+        let genesis_hash = genesis_hash(&env);
+        let txs = (0..NUM_SHARDS)
+            .map(|i| tx(i, (i + 1) % NUM_SHARDS, 1, vec![transfer(100)], genesis_hash))
+            .collect::<Vec<_>>();
+        let blocks = produce_blocks_with_txs(&mut env, txs.clone());
+
+        let chain_store = env.clients[0].chain.store();
+        let runtime_adapter = &env.clients[0].runtime_adapter;
+        let epoch_id = blocks[0].header().epoch_id();
+        let shard_id = |account_id: &AccountId| {
+            runtime_adapter.account_id_to_shard_id(account_id, epoch_id).unwrap()
+        };
+        // Receipts of transfers within a shard are local and are not included in chunks.
+        let mut expected = txs
+            .iter()
+            .map(|tx| &tx.transaction)
+            .filter(|tx| shard_id(&tx.signer_id) != shard_id(&tx.receiver_id))
+            .map(|tx| (tx.signer_id.clone(), tx.receiver_id.clone()))
+            .collect::<Vec<_>>();
+        assert!(!expected.is_empty());
+
+        let mut dumped = blocks
+            .iter()
+            .flat_map(|block| receipt_dump(chain_store, block))
+            // Gas refunds are issued by the system account and depend on gas price changes.
+            .filter(|receipt| !receipt.predecessor_id.is_system())
+            .map(|receipt| (receipt.predecessor_id, receipt.receiver_id))
+            .collect::<Vec<_>>();
+        expected.sort();
+        dumped.sort();
+        assert_eq!(dumped, expected);
+    }
+}